@@ -0,0 +1,490 @@
+use ash::version::{DeviceV1_0, InstanceV1_0};
+use ash::vk;
+use std::os::raw::c_void;
+use std::ptr;
+
+use crate::error::{RendererError, RendererResult, VkResultExt};
+use crate::memory::find_memory_type;
+
+// Blocks are allocated in this size, resources larger than half a block get their own.
+const DEFAULT_BLOCK_SIZE: vk::DeviceSize = 64 * 1024 * 1024;
+
+// A range inside one of the allocator's blocks. Hand it back with Allocator::free exactly once.
+#[derive(Debug)]
+pub struct Allocation {
+    pub memory: vk::DeviceMemory,
+    pub offset: vk::DeviceSize,
+    pub size: vk::DeviceSize,
+    block_index: usize,
+    mapped_ptr: *mut c_void,
+}
+
+impl Allocation {
+    // host visible blocks stay mapped for their whole lifetime
+    pub fn mapped_ptr(&self) -> Option<*mut c_void> {
+        if self.mapped_ptr.is_null() {
+            None
+        } else {
+            Some(self.mapped_ptr)
+        }
+    }
+}
+
+struct MemoryBlock {
+    memory: vk::DeviceMemory,
+    memory_type_index: u32,
+    // buffers and optimal tiling images never share a block, which keeps
+    // bufferImageGranularity out of the offset math
+    is_linear: bool,
+    is_dedicated: bool,
+    // sorted by offset, adjacent ranges are merged on free
+    free_ranges: Vec<(vk::DeviceSize, vk::DeviceSize)>,
+    allocation_count: usize,
+    mapped_ptr: *mut c_void,
+}
+
+impl MemoryBlock {
+    // first fit, returns the aligned offset
+    fn allocate(
+        &mut self,
+        size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+    ) -> Option<vk::DeviceSize> {
+        for i in 0..self.free_ranges.len() {
+            let (range_offset, range_size) = self.free_ranges[i];
+            let offset = align_up(range_offset, alignment);
+            let padding = offset - range_offset;
+            if padding + size > range_size {
+                continue;
+            }
+            self.free_ranges.remove(i);
+            let end = offset + size;
+            let range_end = range_offset + range_size;
+            if end < range_end {
+                self.free_ranges.insert(i, (end, range_end - end));
+            }
+            if padding > 0 {
+                self.free_ranges.insert(i, (range_offset, padding));
+            }
+            self.allocation_count += 1;
+            return Some(offset);
+        }
+        None
+    }
+
+    fn free(&mut self, offset: vk::DeviceSize, size: vk::DeviceSize) {
+        let i = self
+            .free_ranges
+            .iter()
+            .position(|&(range_offset, _)| range_offset > offset)
+            .unwrap_or(self.free_ranges.len());
+        self.free_ranges.insert(i, (offset, size));
+        // merge with the following range, then with the preceding one
+        if i + 1 < self.free_ranges.len() && offset + size == self.free_ranges[i + 1].0 {
+            self.free_ranges[i].1 += self.free_ranges[i + 1].1;
+            self.free_ranges.remove(i + 1);
+        }
+        if i > 0 && self.free_ranges[i - 1].0 + self.free_ranges[i - 1].1 == offset {
+            self.free_ranges[i - 1].1 += self.free_ranges[i].1;
+            self.free_ranges.remove(i);
+        }
+        self.allocation_count -= 1;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.allocation_count == 0
+    }
+
+    // whether a new allocation may be placed in this block
+    fn is_shareable(&self, memory_type_index: u32, is_linear: bool) -> bool {
+        self.memory_type_index == memory_type_index
+            && self.is_linear == is_linear
+            && !self.is_dedicated
+    }
+}
+
+// Large resources would waste most of a shared block, so they get one of their own.
+fn needs_dedicated_block(size: vk::DeviceSize, block_size: vk::DeviceSize) -> bool {
+    size > block_size / 2
+}
+
+// Vulkan alignments are powers of two
+fn align_up(value: vk::DeviceSize, alignment: vk::DeviceSize) -> vk::DeviceSize {
+    let mask = alignment.max(1) - 1;
+    (value + mask) & !mask
+}
+
+// Sub-allocates buffers and images from a few large vk::DeviceMemory blocks instead of
+// one vkAllocateMemory per resource, so maxMemoryAllocationCount isn't a concern.
+// Empty blocks are kept around (one per memory type) so the next allocation reuses them.
+pub struct Allocator {
+    device: ash::Device,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    max_memory_allocation_count: u32,
+    block_size: vk::DeviceSize,
+    blocks: Vec<Option<MemoryBlock>>,
+}
+
+impl Allocator {
+    pub fn new(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        device: &ash::Device,
+    ) -> Self {
+        let memory_properties =
+            unsafe { instance.get_physical_device_memory_properties(physical_device) };
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        Allocator {
+            device: device.clone(),
+            memory_properties,
+            max_memory_allocation_count: properties.limits.max_memory_allocation_count,
+            block_size: DEFAULT_BLOCK_SIZE,
+            blocks: vec![],
+        }
+    }
+
+    // number of vk::DeviceMemory objects currently allocated
+    fn block_count(&self) -> usize {
+        self.blocks.iter().filter(|block| block.is_some()).count()
+    }
+
+    pub fn allocate(
+        &mut self,
+        memory_requirements: vk::MemoryRequirements,
+        required_properties: vk::MemoryPropertyFlags,
+        is_linear: bool,
+    ) -> RendererResult<Allocation> {
+        let memory_type_index = find_memory_type(
+            &self.memory_properties,
+            memory_requirements.memory_type_bits,
+            required_properties,
+        )?;
+        let size = memory_requirements.size;
+        let alignment = memory_requirements.alignment;
+
+        let is_dedicated = needs_dedicated_block(size, self.block_size);
+        if !is_dedicated {
+            for (block_index, block) in self.blocks.iter_mut().enumerate() {
+                let block = match block {
+                    Some(block) if block.is_shareable(memory_type_index, is_linear) => block,
+                    _ => continue,
+                };
+                if let Some(offset) = block.allocate(size, alignment) {
+                    return Ok(Allocation {
+                        memory: block.memory,
+                        offset,
+                        size,
+                        block_index,
+                        mapped_ptr: offset_ptr(block.mapped_ptr, offset),
+                    });
+                }
+            }
+        }
+
+        let block_size = if is_dedicated { size } else { self.block_size };
+        let mut block =
+            self.allocate_block(block_size, memory_type_index, is_linear, is_dedicated)?;
+        let offset = block
+            .allocate(size, alignment)
+            .ok_or(RendererError::NoSuitableMemoryType)?;
+        let mapped_ptr = offset_ptr(block.mapped_ptr, offset);
+        let memory = block.memory;
+        let block_index = match self.blocks.iter().position(|block| block.is_none()) {
+            Some(block_index) => {
+                self.blocks[block_index] = Some(block);
+                block_index
+            }
+            None => {
+                self.blocks.push(Some(block));
+                self.blocks.len() - 1
+            }
+        };
+        Ok(Allocation {
+            memory,
+            offset,
+            size,
+            block_index,
+            mapped_ptr,
+        })
+    }
+
+    fn allocate_block(
+        &self,
+        size: vk::DeviceSize,
+        memory_type_index: u32,
+        is_linear: bool,
+        is_dedicated: bool,
+    ) -> RendererResult<MemoryBlock> {
+        if self.block_count() as u32 >= self.max_memory_allocation_count {
+            return Err(RendererError::from_vk(
+                "maxMemoryAllocationCount reached",
+                vk::Result::ERROR_TOO_MANY_OBJECTS,
+            ));
+        }
+        let memory_allocate_info = vk::MemoryAllocateInfo {
+            allocation_size: size,
+            memory_type_index,
+            ..Default::default()
+        };
+        let memory = unsafe {
+            self.device
+                .allocate_memory(&memory_allocate_info, None)
+                .context("Failed to allocate memory block!")?
+        };
+        let is_host_visible = self.memory_properties.memory_types[memory_type_index as usize]
+            .property_flags
+            .contains(vk::MemoryPropertyFlags::HOST_VISIBLE);
+        let mapped_ptr = if is_host_visible {
+            let mapped_ptr = unsafe {
+                self.device
+                    .map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+                    .context("Failed to map memory block!")
+            };
+            match mapped_ptr {
+                Ok(mapped_ptr) => mapped_ptr,
+                Err(err) => {
+                    unsafe { self.device.free_memory(memory, None) };
+                    return Err(err);
+                }
+            }
+        } else {
+            ptr::null_mut()
+        };
+        Ok(MemoryBlock {
+            memory,
+            memory_type_index,
+            is_linear,
+            is_dedicated,
+            free_ranges: vec![(0, size)],
+            allocation_count: 0,
+            mapped_ptr,
+        })
+    }
+
+    pub fn free(&mut self, allocation: &Allocation) {
+        let block = match &mut self.blocks[allocation.block_index] {
+            Some(block) => block,
+            None => return,
+        };
+        block.free(allocation.offset, allocation.size);
+        if !block.is_empty() {
+            return;
+        }
+        let memory_type_index = block.memory_type_index;
+        let is_linear = block.is_linear;
+        // keep a single empty block per memory type around for reuse
+        let is_spare = !block.is_dedicated
+            && !self.blocks.iter().enumerate().any(|(i, other)| match other {
+                Some(other) => {
+                    i != allocation.block_index
+                        && other.is_empty()
+                        && !other.is_dedicated
+                        && other.memory_type_index == memory_type_index
+                        && other.is_linear == is_linear
+                }
+                None => false,
+            });
+        if !is_spare {
+            if let Some(block) = self.blocks[allocation.block_index].take() {
+                self.free_block(block);
+            }
+        }
+    }
+
+    fn free_block(&self, block: MemoryBlock) {
+        unsafe {
+            if !block.mapped_ptr.is_null() {
+                self.device.unmap_memory(block.memory);
+            }
+            self.device.free_memory(block.memory, None);
+        }
+    }
+
+    pub fn create_buffer(
+        &mut self,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        required_properties: vk::MemoryPropertyFlags,
+    ) -> RendererResult<(vk::Buffer, Allocation)> {
+        let buffer_create_info = vk::BufferCreateInfo {
+            size,
+            usage,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            ..Default::default()
+        };
+        let buffer = unsafe {
+            self.device
+                .create_buffer(&buffer_create_info, None)
+                .context("Failed to create Buffer!")?
+        };
+        let memory_requirements = unsafe { self.device.get_buffer_memory_requirements(buffer) };
+        let allocation = match self.allocate(memory_requirements, required_properties, true) {
+            Ok(allocation) => allocation,
+            Err(err) => {
+                unsafe { self.device.destroy_buffer(buffer, None) };
+                return Err(err);
+            }
+        };
+        let result = unsafe {
+            self.device
+                .bind_buffer_memory(buffer, allocation.memory, allocation.offset)
+                .context("Failed to bind Buffer memory!")
+        };
+        if let Err(err) = result {
+            self.destroy_buffer(buffer, &allocation);
+            return Err(err);
+        }
+        Ok((buffer, allocation))
+    }
+
+    pub fn destroy_buffer(&mut self, buffer: vk::Buffer, allocation: &Allocation) {
+        unsafe { self.device.destroy_buffer(buffer, None) };
+        self.free(allocation);
+    }
+
+    pub fn create_image(
+        &mut self,
+        image_create_info: &vk::ImageCreateInfo,
+        required_properties: vk::MemoryPropertyFlags,
+    ) -> RendererResult<(vk::Image, Allocation)> {
+        let image = unsafe {
+            self.device
+                .create_image(image_create_info, None)
+                .context("Failed to create Image!")?
+        };
+        let memory_requirements = unsafe { self.device.get_image_memory_requirements(image) };
+        let is_linear = image_create_info.tiling == vk::ImageTiling::LINEAR;
+        let allocation = match self.allocate(memory_requirements, required_properties, is_linear) {
+            Ok(allocation) => allocation,
+            Err(err) => {
+                unsafe { self.device.destroy_image(image, None) };
+                return Err(err);
+            }
+        };
+        let result = unsafe {
+            self.device
+                .bind_image_memory(image, allocation.memory, allocation.offset)
+                .context("Failed to bind Image memory!")
+        };
+        if let Err(err) = result {
+            self.destroy_image(image, &allocation);
+            return Err(err);
+        }
+        Ok((image, allocation))
+    }
+
+    pub fn destroy_image(&mut self, image: vk::Image, allocation: &Allocation) {
+        unsafe { self.device.destroy_image(image, None) };
+        self.free(allocation);
+    }
+
+    // Frees every block, must run before the device is destroyed.
+    pub fn destroy(&mut self) {
+        for block in self.blocks.drain(..).flatten().collect::<Vec<_>>() {
+            self.free_block(block);
+        }
+    }
+}
+
+// Only does something when a build failed before the allocator was handed over,
+// otherwise destroy() already emptied it.
+impl Drop for Allocator {
+    fn drop(&mut self) {
+        self.destroy();
+    }
+}
+
+fn offset_ptr(mapped_ptr: *mut c_void, offset: vk::DeviceSize) -> *mut c_void {
+    if mapped_ptr.is_null() {
+        mapped_ptr
+    } else {
+        unsafe { (mapped_ptr as *mut u8).add(offset as usize) as *mut c_void }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_block(size: vk::DeviceSize) -> MemoryBlock {
+        MemoryBlock {
+            memory: vk::DeviceMemory::null(),
+            memory_type_index: 0,
+            is_linear: true,
+            is_dedicated: false,
+            free_ranges: vec![(0, size)],
+            allocation_count: 0,
+            mapped_ptr: ptr::null_mut(),
+        }
+    }
+
+    #[test]
+    fn allocate_free_and_reallocate() {
+        let mut block = memory_block(1024);
+        assert_eq!(block.allocate(256, 1), Some(0));
+        assert_eq!(block.allocate(256, 1), Some(256));
+        block.free(0, 256);
+        assert_eq!(block.free_ranges, vec![(0, 256), (512, 512)]);
+        // first fit reuses the hole at the front
+        assert_eq!(block.allocate(128, 1), Some(0));
+        assert_eq!(block.free_ranges, vec![(128, 128), (512, 512)]);
+    }
+
+    #[test]
+    fn free_merges_both_neighbours() {
+        let mut block = memory_block(768);
+        let offsets: Vec<_> = (0..3).map(|_| block.allocate(256, 1).unwrap()).collect();
+        assert_eq!(offsets, vec![0, 256, 512]);
+        assert!(block.free_ranges.is_empty());
+        block.free(0, 256);
+        block.free(512, 256);
+        assert_eq!(block.free_ranges, vec![(0, 256), (512, 256)]);
+        // the middle range joins the one before and the one after it
+        block.free(256, 256);
+        assert_eq!(block.free_ranges, vec![(0, 768)]);
+        assert!(block.is_empty());
+    }
+
+    #[test]
+    fn allocate_pads_to_alignment() {
+        let mut block = memory_block(1024);
+        assert_eq!(block.allocate(10, 1), Some(0));
+        assert_eq!(block.allocate(100, 256), Some(256));
+        // the padding stays free for smaller allocations
+        assert_eq!(block.free_ranges, vec![(10, 246), (356, 668)]);
+        assert_eq!(block.allocate(200, 8), Some(16));
+        block.free(256, 100);
+        block.free(16, 200);
+        block.free(0, 10);
+        assert_eq!(block.free_ranges, vec![(0, 1024)]);
+    }
+
+    #[test]
+    fn allocate_fails_when_exhausted() {
+        let mut block = memory_block(512);
+        assert_eq!(block.allocate(512, 1), Some(0));
+        assert_eq!(block.allocate(1, 1), None);
+        block.free(0, 512);
+        // padding counts against the range, 256 + 300 doesn't fit in 512
+        assert_eq!(block.allocate(1, 1), Some(0));
+        assert_eq!(block.allocate(300, 256), None);
+        assert_eq!(block.allocation_count, 1);
+    }
+
+    #[test]
+    fn dedicated_blocks_for_large_resources() {
+        assert!(!needs_dedicated_block(32, 64));
+        assert!(needs_dedicated_block(33, 64));
+        let mut block = memory_block(64);
+        assert!(block.is_shareable(0, true));
+        block.is_dedicated = true;
+        assert!(!block.is_shareable(0, true));
+    }
+
+    #[test]
+    fn linear_and_optimal_resources_use_separate_blocks() {
+        let block = memory_block(64);
+        assert!(!block.is_shareable(0, false));
+        assert!(!block.is_shareable(1, true));
+    }
+}
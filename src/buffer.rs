@@ -0,0 +1,225 @@
+use ash::version::DeviceV1_0;
+use ash::vk;
+
+use crate::allocator::{Allocation, Allocator};
+use crate::command::{begin_single_time_command, end_single_time_command};
+use crate::error::{RendererError, RendererResult};
+use crate::vertex::Vertex;
+
+// Records a one time copy and waits for the queue, fine for uploads done at load time.
+pub fn copy_buffer(
+    device: &ash::Device,
+    submit_queue: vk::Queue,
+    command_pool: vk::CommandPool,
+    src_buffer: vk::Buffer,
+    dst_buffer: vk::Buffer,
+    size: vk::DeviceSize,
+) -> RendererResult<()> {
+    let copy_regions = [vk::BufferCopy {
+        src_offset: 0,
+        dst_offset: 0,
+        size,
+    }];
+    let command_buffer = begin_single_time_command(device, command_pool)?;
+    unsafe { device.cmd_copy_buffer(command_buffer, src_buffer, dst_buffer, &copy_regions) };
+    end_single_time_command(device, submit_queue, command_pool, command_buffer)
+}
+
+// A HOST_VISIBLE buffer holding a copy of `data`, the allocator keeps it mapped.
+// Vulkan has no zero sized buffers, so empty data is an error.
+pub fn create_staging_buffer<T: Copy>(
+    allocator: &mut Allocator,
+    data: &[T],
+) -> RendererResult<(vk::Buffer, Allocation)> {
+    let buffer_size = std::mem::size_of_val(data) as vk::DeviceSize;
+    if buffer_size == 0 {
+        return Err(RendererError::EmptyBuffer);
+    }
+    let (staging_buffer, staging_allocation) = allocator.create_buffer(
+        buffer_size,
+        vk::BufferUsageFlags::TRANSFER_SRC,
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+    )?;
+    match staging_allocation.mapped_ptr() {
+        Some(data_ptr) => unsafe {
+            (data_ptr as *mut T).copy_from_nonoverlapping(data.as_ptr(), data.len());
+        },
+        None => {
+            allocator.destroy_buffer(staging_buffer, &staging_allocation);
+            return Err(RendererError::MemoryNotMapped);
+        }
+    }
+    Ok((staging_buffer, staging_allocation))
+}
+
+// Uploads `data` into a new DEVICE_LOCAL buffer through a host visible staging buffer.
+pub fn create_device_local_buffer<T: Copy>(
+    device: &ash::Device,
+    allocator: &mut Allocator,
+    command_pool: vk::CommandPool,
+    submit_queue: vk::Queue,
+    usage: vk::BufferUsageFlags,
+    data: &[T],
+) -> RendererResult<(vk::Buffer, Allocation)> {
+    let buffer_size = std::mem::size_of_val(data) as vk::DeviceSize;
+    let (staging_buffer, staging_allocation) = create_staging_buffer(allocator, data)?;
+
+    let (buffer, allocation) = match allocator.create_buffer(
+        buffer_size,
+        vk::BufferUsageFlags::TRANSFER_DST | usage,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+    ) {
+        Ok(buffer) => buffer,
+        Err(err) => {
+            allocator.destroy_buffer(staging_buffer, &staging_allocation);
+            return Err(err);
+        }
+    };
+    let result = copy_buffer(
+        device,
+        submit_queue,
+        command_pool,
+        staging_buffer,
+        buffer,
+        buffer_size,
+    );
+    allocator.destroy_buffer(staging_buffer, &staging_allocation);
+    if let Err(err) = result {
+        allocator.destroy_buffer(buffer, &allocation);
+        return Err(err);
+    }
+    Ok((buffer, allocation))
+}
+
+pub fn create_vertex_buffer(
+    device: &ash::Device,
+    allocator: &mut Allocator,
+    command_pool: vk::CommandPool,
+    submit_queue: vk::Queue,
+    vertices: &[Vertex],
+) -> RendererResult<(vk::Buffer, Allocation)> {
+    create_device_local_buffer(
+        device,
+        allocator,
+        command_pool,
+        submit_queue,
+        vk::BufferUsageFlags::VERTEX_BUFFER,
+        vertices,
+    )
+}
+
+pub struct IndexBufferStuff {
+    pub buffer: vk::Buffer,
+    pub allocation: Allocation,
+    pub index_type: vk::IndexType,
+    pub index_count: u32,
+}
+
+// UINT16 halves the buffer whenever every vertex is addressable with it. 0xFFFF is left out
+// so the choice stays valid if primitive restart is ever enabled.
+pub fn index_type_for(vertex_count: usize) -> vk::IndexType {
+    if vertex_count <= u16::MAX as usize {
+        vk::IndexType::UINT16
+    } else {
+        vk::IndexType::UINT32
+    }
+}
+
+pub fn create_index_buffer(
+    device: &ash::Device,
+    allocator: &mut Allocator,
+    command_pool: vk::CommandPool,
+    submit_queue: vk::Queue,
+    indices: &[u32],
+    vertex_count: usize,
+) -> RendererResult<IndexBufferStuff> {
+    // also keeps the u16 conversion below lossless
+    if let Some(&index) = indices.iter().find(|&&index| index as usize >= vertex_count) {
+        return Err(RendererError::IndexOutOfRange {
+            index,
+            vertex_count,
+        });
+    }
+    let index_type = index_type_for(vertex_count);
+    let (buffer, allocation) = if index_type == vk::IndexType::UINT16 {
+        let indices: Vec<u16> = indices.iter().map(|&index| index as u16).collect();
+        create_device_local_buffer(
+            device,
+            allocator,
+            command_pool,
+            submit_queue,
+            vk::BufferUsageFlags::INDEX_BUFFER,
+            &indices,
+        )?
+    } else {
+        create_device_local_buffer(
+            device,
+            allocator,
+            command_pool,
+            submit_queue,
+            vk::BufferUsageFlags::INDEX_BUFFER,
+            indices,
+        )?
+    };
+    Ok(IndexBufferStuff {
+        buffer,
+        allocation,
+        index_type,
+        index_count: indices.len() as u32,
+    })
+}
+
+// A vertex buffer and the index buffer drawing it, swapped as one by Core::load_model.
+pub struct GeometryStuff {
+    pub vertex_buffer: vk::Buffer,
+    pub vertex_allocation: Allocation,
+    pub index_buffer: IndexBufferStuff,
+}
+
+pub fn create_geometry(
+    device: &ash::Device,
+    allocator: &mut Allocator,
+    command_pool: vk::CommandPool,
+    submit_queue: vk::Queue,
+    vertices: &[Vertex],
+    indices: &[u32],
+) -> RendererResult<GeometryStuff> {
+    let (vertex_buffer, vertex_allocation) =
+        create_vertex_buffer(device, allocator, command_pool, submit_queue, vertices)?;
+    match create_index_buffer(
+        device,
+        allocator,
+        command_pool,
+        submit_queue,
+        indices,
+        vertices.len(),
+    ) {
+        Ok(index_buffer) => Ok(GeometryStuff {
+            vertex_buffer,
+            vertex_allocation,
+            index_buffer,
+        }),
+        Err(err) => {
+            allocator.destroy_buffer(vertex_buffer, &vertex_allocation);
+            Err(err)
+        }
+    }
+}
+
+pub fn destroy_geometry(allocator: &mut Allocator, geometry: &GeometryStuff) {
+    allocator.destroy_buffer(geometry.vertex_buffer, &geometry.vertex_allocation);
+    allocator.destroy_buffer(geometry.index_buffer.buffer, &geometry.index_buffer.allocation);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_type_for_vertex_count() {
+        assert_eq!(index_type_for(3), vk::IndexType::UINT16);
+        // the largest index is 65534, primitive restart's 0xFFFF stays unused
+        assert_eq!(index_type_for(65535), vk::IndexType::UINT16);
+        assert_eq!(index_type_for(65536), vk::IndexType::UINT32);
+    }
+}
@@ -0,0 +1,64 @@
+// Undoes a half finished build. Every step pushes how to destroy what it created and
+// an early return runs them newest first. Once the finished struct owns everything,
+// disarm() hands the cleanup over to its Drop.
+pub struct CleanupGuard {
+    cleanups: Vec<Box<dyn FnOnce()>>,
+}
+
+impl CleanupGuard {
+    pub fn new() -> Self {
+        CleanupGuard { cleanups: vec![] }
+    }
+
+    pub fn push(&mut self, cleanup: impl FnOnce() + 'static) {
+        self.cleanups.push(Box::new(cleanup));
+    }
+
+    // for cleanups that need the device, instance or an extension loader to run
+    pub fn push_with<T: Clone + 'static>(&mut self, owner: &T, cleanup: impl FnOnce(&T) + 'static) {
+        let owner = owner.clone();
+        self.push(move || cleanup(&owner));
+    }
+
+    pub fn disarm(mut self) {
+        self.cleanups.clear();
+    }
+}
+
+impl Drop for CleanupGuard {
+    fn drop(&mut self) {
+        while let Some(cleanup) = self.cleanups.pop() {
+            cleanup();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn runs_cleanups_newest_first() {
+        let order = Rc::new(RefCell::new(vec![]));
+        {
+            let mut guard = CleanupGuard::new();
+            for step in 0..3 {
+                let order = order.clone();
+                guard.push(move || order.borrow_mut().push(step));
+            }
+        }
+        assert_eq!(*order.borrow(), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn disarm_skips_cleanups() {
+        let order = Rc::new(RefCell::new(vec![]));
+        let mut guard = CleanupGuard::new();
+        let cleanup_order = order.clone();
+        guard.push_with(&1, move |step| cleanup_order.borrow_mut().push(*step));
+        guard.disarm();
+        assert!(order.borrow().is_empty());
+    }
+}
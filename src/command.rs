@@ -2,24 +2,30 @@ use crate::queue::find_queue_families;
 use ash::version::DeviceV1_0;
 use ash::vk;
 use std::ptr;
+
+use crate::debug::DebugNamer;
+use crate::error::{RendererError, RendererResult, VkResultExt};
+use crate::push_constant::{cmd_push_constants, PushConstants};
 pub fn create_command_pool(
     instance: &ash::Instance,
     physical_device: &vk::PhysicalDevice,
     device: &ash::Device,
     surface: &vk::SurfaceKHR,
-) -> vk::CommandPool {
-    let queue_family_indices = find_queue_families(instance, &physical_device, &surface);
+) -> RendererResult<vk::CommandPool> {
+    let queue_family_indices = find_queue_families(instance, physical_device, surface)?;
     let command_pool_create_info = vk::CommandPoolCreateInfo {
         s_type: vk::StructureType::COMMAND_POOL_CREATE_INFO,
         p_next: ptr::null(),
         flags: vk::CommandPoolCreateFlags::empty(),
-        queue_family_index: queue_family_indices.graphics_family.unwrap(),
+        queue_family_index: queue_family_indices
+            .graphics_family
+            .ok_or(RendererError::NoSuitableQueueFamily)?,
     };
 
     unsafe {
         device
             .create_command_pool(&command_pool_create_info, None)
-            .expect("Failed to create Command Pool!")
+            .context("Failed to create Command Pool!")
     }
 }
 // pub fn create_command_buffers(device:&ash::Device,command_pool:&vk::CommandPool,render_pass:&vk::RenderPass,framebuffer:&vk::Framebuffer,swap_chain_extent:&vk::Extent2D,pipeline:&vk::Pipeline)->vk::CommandBuffer {
@@ -71,14 +77,91 @@ pub fn create_command_pool(
 //     };
 // }
 
+// Allocates and begins a ONE_TIME_SUBMIT command buffer for an upload.
+pub fn begin_single_time_command(
+    device: &ash::Device,
+    command_pool: vk::CommandPool,
+) -> RendererResult<vk::CommandBuffer> {
+    let command_buffer_allocate_info = vk::CommandBufferAllocateInfo {
+        command_buffer_count: 1,
+        command_pool,
+        level: vk::CommandBufferLevel::PRIMARY,
+        ..Default::default()
+    };
+    let command_buffer_begin_info = vk::CommandBufferBeginInfo {
+        flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+        ..Default::default()
+    };
+    let command_buffer = unsafe {
+        device
+            .allocate_command_buffers(&command_buffer_allocate_info)
+            .context("Failed to allocate Command Buffers!")?[0]
+    };
+    let result = unsafe {
+        device
+            .begin_command_buffer(command_buffer, &command_buffer_begin_info)
+            .context("Failed to begin recording Command Buffer at beginning!")
+    };
+    if let Err(err) = result {
+        unsafe { device.free_command_buffers(command_pool, &[command_buffer]) };
+        return Err(err);
+    }
+    Ok(command_buffer)
+}
+
+// Ends, submits and waits for the queue, fine for uploads done at load time.
+// The command buffer is freed whether or not the submit worked.
+pub fn end_single_time_command(
+    device: &ash::Device,
+    submit_queue: vk::Queue,
+    command_pool: vk::CommandPool,
+    command_buffer: vk::CommandBuffer,
+) -> RendererResult<()> {
+    let result = unsafe {
+        device
+            .end_command_buffer(command_buffer)
+            .context("Failed to record Command Buffer at Ending!")
+            .and_then(|_| {
+                let submit_infos = [vk::SubmitInfo {
+                    command_buffer_count: 1,
+                    p_command_buffers: &command_buffer,
+                    ..Default::default()
+                }];
+                device
+                    .queue_submit(submit_queue, &submit_infos, vk::Fence::null())
+                    .context("Failed to execute queue submit.")?;
+                device
+                    .queue_wait_idle(submit_queue)
+                    .context("Failed to wait queue idle.")
+            })
+    };
+    unsafe { device.free_command_buffers(command_pool, &[command_buffer]) };
+    result
+}
+
+// What every recorded command buffer binds and draws.
+pub struct DrawStuff<'a> {
+    pub graphics_pipeline: vk::Pipeline,
+    pub pipeline_layout: vk::PipelineLayout,
+    // one per framebuffer, in the same order
+    pub descriptor_sets: &'a [vk::DescriptorSet],
+    pub push_constant_range: vk::PushConstantRange,
+    pub push_constants: PushConstants,
+    pub vertex_buffer: vk::Buffer,
+    pub index_buffer: vk::Buffer,
+    pub index_type: vk::IndexType,
+    pub index_count: u32,
+}
+
 pub fn create_command_buffers(
     device: &ash::Device,
     command_pool: &vk::CommandPool,
-    graphics_pipeline: &vk::Pipeline,
-    framebuffers: &Vec<vk::Framebuffer>,
+    framebuffers: &[vk::Framebuffer],
     render_pass: &vk::RenderPass,
     surface_extent: &vk::Extent2D,
-) -> Vec<vk::CommandBuffer> {
+    draw_stuff: &DrawStuff,
+    debug_namer: &DebugNamer,
+) -> RendererResult<Vec<vk::CommandBuffer>> {
     let command_buffer_allocate_info = vk::CommandBufferAllocateInfo {
         s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
         p_next: ptr::null(),
@@ -90,65 +173,121 @@ pub fn create_command_buffers(
     let command_buffers = unsafe {
         device
             .allocate_command_buffers(&command_buffer_allocate_info)
-            .expect("Failed to allocate Command Buffers!")
+            .context("Failed to allocate Command Buffers!")?
     };
 
-    for (i, &command_buffer) in command_buffers.iter().enumerate() {
-        let command_buffer_begin_info = vk::CommandBufferBeginInfo {
-            ..Default::default()
-        };
-
-        unsafe {
-            device
-                .begin_command_buffer(command_buffer, &command_buffer_begin_info)
-                .expect("Failed to begin recording Command Buffer at beginning!");
-        }
-
-        let clear_values = [
-            vk::ClearValue {
-            color: vk::ClearColorValue {
-                float32: [0.0, 0.0, 0.0, 1.0],
-            }},
-            vk::ClearValue{
-                depth_stencil: vk::ClearDepthStencilValue{
-                    depth: 1.0,
-                    stencil: 0
-                }
+    // a failure part way frees the whole batch, the caller never sees half of it
+    let result = command_buffers
+        .iter()
+        .enumerate()
+        .try_for_each(|(i, &command_buffer)| -> RendererResult<()> {
+            let command_buffer_begin_info = vk::CommandBufferBeginInfo {
+                ..Default::default()
+            };
+
+            unsafe {
+                device
+                    .begin_command_buffer(command_buffer, &command_buffer_begin_info)
+                    .context("Failed to begin recording Command Buffer at beginning!")?;
             }
-        ];
 
-        let render_pass_begin_info = vk::RenderPassBeginInfo {
-            render_pass: *render_pass,
-            framebuffer: framebuffers[i],
-            render_area: vk::Rect2D {
+            let clear_values = [
+                vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 1.0],
+                }},
+                vk::ClearValue{
+                    depth_stencil: vk::ClearDepthStencilValue{
+                        depth: 1.0,
+                        stencil: 0
+                    }
+                }
+            ];
+
+            let render_pass_begin_info = vk::RenderPassBeginInfo {
+                render_pass: *render_pass,
+                framebuffer: framebuffers[i],
+                render_area: vk::Rect2D {
+                    offset: vk::Offset2D { x: 0, y: 0 },
+                    extent: *surface_extent,
+                },
+                clear_value_count: clear_values.len() as u32,
+                p_clear_values: clear_values.as_ptr(),
+                ..Default::default()
+            };
+
+            // the pipeline leaves both dynamic, so it is not rebuilt on resize
+            let viewport = vk::Viewport {
+                x: 0.0,
+                y: 0.0,
+                width: surface_extent.width as f32,
+                height: surface_extent.height as f32,
+                min_depth: 0.0,
+                max_depth: 1.0,
+            };
+            let scissor = vk::Rect2D {
                 offset: vk::Offset2D { x: 0, y: 0 },
                 extent: *surface_extent,
-            },
-            clear_value_count: clear_values.len() as u32,
-            p_clear_values: clear_values.as_ptr(),
-            ..Default::default()
-        };
-
-        unsafe {
-            device.cmd_begin_render_pass(
-                command_buffer,
-                &render_pass_begin_info,
-                vk::SubpassContents::INLINE,
-            );
-            device.cmd_bind_pipeline(
-                command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                *graphics_pipeline,
-            );
-            device.cmd_draw(command_buffer, 3, 1, 0, 0);
-
-            device.cmd_end_render_pass(command_buffer);
-
-            device
-                .end_command_buffer(command_buffer)
-                .expect("Failed to record Command Buffer at Ending!");
-        }
+            };
+
+            unsafe {
+                debug_namer.begin_label(command_buffer, "Main Render Pass", [0.2, 0.6, 1.0, 1.0]);
+                device.cmd_begin_render_pass(
+                    command_buffer,
+                    &render_pass_begin_info,
+                    vk::SubpassContents::INLINE,
+                );
+                device.cmd_bind_pipeline(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    draw_stuff.graphics_pipeline,
+                );
+                device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+                device.cmd_set_scissor(command_buffer, 0, &[scissor]);
+                device.cmd_bind_vertex_buffers(
+                    command_buffer,
+                    0,
+                    &[draw_stuff.vertex_buffer],
+                    &[0],
+                );
+                device.cmd_bind_index_buffer(
+                    command_buffer,
+                    draw_stuff.index_buffer,
+                    0,
+                    draw_stuff.index_type,
+                );
+                device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    draw_stuff.pipeline_layout,
+                    0,
+                    &[draw_stuff.descriptor_sets[i]],
+                    &[],
+                );
+                cmd_push_constants(
+                    device,
+                    command_buffer,
+                    draw_stuff.pipeline_layout,
+                    &draw_stuff.push_constant_range,
+                    &draw_stuff.push_constants,
+                );
+                device.cmd_draw_indexed(command_buffer, draw_stuff.index_count, 1, 0, 0, 0);
+
+                device.cmd_end_render_pass(command_buffer);
+                debug_namer.end_label(command_buffer);
+
+                device
+                    .end_command_buffer(command_buffer)
+                    .context("Failed to record Command Buffer at Ending!")?;
+            }
+            Ok(())
+        });
+    let result =
+        result.and_then(|_| debug_namer.set_object_names(&command_buffers, "Command Buffer"));
+    if let Err(err) = result {
+        unsafe { device.free_command_buffers(*command_pool, &command_buffers) };
+        return Err(err);
     }
 
-    command_buffers
+    Ok(command_buffers)
 }
@@ -0,0 +1,83 @@
+use crate::debug::ValidationInfo;
+use crate::device_features::DeviceRequirements;
+use crate::instance::{parse_api_version, AppInfo};
+use crate::physical_device::DeviceSelection;
+use crate::texture::SamplerConfig;
+
+// Options shared by Core and HeadlessCore, set up like winit's WindowBuilder.
+#[derive(Debug, Clone)]
+pub struct CoreConfig {
+    pub device_selection: DeviceSelection,
+    pub device_requirements: DeviceRequirements,
+    pub validation: ValidationInfo,
+    pub app_info: AppInfo,
+    pub sampler: SamplerConfig,
+}
+
+impl Default for CoreConfig {
+    fn default() -> Self {
+        CoreConfig {
+            device_selection: DeviceSelection::Best,
+            device_requirements: DeviceRequirements::default(),
+            validation: ValidationInfo::default(),
+            app_info: AppInfo::default(),
+            sampler: SamplerConfig::default(),
+        }
+    }
+}
+
+impl CoreConfig {
+    pub fn from_env() -> Self {
+        let mut config = CoreConfig::default()
+            .with_device_selection(DeviceSelection::from_env())
+            .with_device_requirements(DeviceRequirements::from_env())
+            .with_validation(ValidationInfo::from_env())
+            .with_sampler(SamplerConfig::from_env());
+        // VULKAN_API_VERSION=1.0 caps the negotiated version, e.g. to try the 1.0 path
+        if let Ok(value) = std::env::var("VULKAN_API_VERSION") {
+            match parse_api_version(&value) {
+                Some(api_version) => config = config.with_max_api_version(api_version),
+                None => log::warn!("Ignoring invalid VULKAN_API_VERSION value: {}", value),
+            }
+        }
+        config
+    }
+
+    pub fn with_device_selection(mut self, device_selection: DeviceSelection) -> Self {
+        self.device_selection = device_selection;
+        self
+    }
+
+    pub fn with_device_requirements(mut self, device_requirements: DeviceRequirements) -> Self {
+        self.device_requirements = device_requirements;
+        self
+    }
+
+    pub fn with_validation(mut self, validation: ValidationInfo) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    pub fn with_application(mut self, name: &str, version: u32) -> Self {
+        self.app_info.application_name = Some(name.to_string());
+        self.app_info.application_version = version;
+        self
+    }
+
+    pub fn with_engine(mut self, name: &str, version: u32) -> Self {
+        self.app_info.engine_name = name.to_string();
+        self.app_info.engine_version = version;
+        self
+    }
+
+    // the instance is created with the highest version the loader supports, up to this one
+    pub fn with_max_api_version(mut self, max_api_version: u32) -> Self {
+        self.app_info.max_api_version = max_api_version;
+        self
+    }
+
+    pub fn with_sampler(mut self, sampler: SamplerConfig) -> Self {
+        self.sampler = sampler;
+        self
+    }
+}
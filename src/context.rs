@@ -0,0 +1,263 @@
+use ash::extensions::ext;
+use ash::version::{DeviceV1_0, InstanceV1_0};
+use ash::vk;
+use ash::Entry;
+
+use crate::allocator::Allocator;
+use crate::buffer::{create_geometry, destroy_geometry, GeometryStuff};
+use crate::cleanup::CleanupGuard;
+use crate::command::create_command_pool;
+use crate::config::CoreConfig;
+use crate::debug::{
+    destroy_debug_messenger, setup_debug_messenger, DebugCallbackData, DebugNamer,
+    ValidationInfo,
+};
+use crate::descriptor::create_descriptor_set_layout;
+use crate::device_features::EnabledDeviceFeatures;
+use crate::error::{RendererError, RendererResult};
+use crate::instance::{create_instance, negotiate_api_version};
+use crate::logical_device::create_logical_device;
+use crate::physical_device::pick_pyhsical_device;
+use crate::push_constant::{check_push_constant_ranges, push_constant_range, PushConstants};
+use crate::queue;
+use crate::surface::{create_surface, SurfaceStuff};
+use crate::texture::{create_sampler, create_white_texture, destroy_texture, TextureStuff};
+use crate::vertex::{INDICES_DATA, VERTICES_DATA};
+
+// Everything Core and HeadlessCore set up the same way, from the instance down to
+// the geometry they draw. Without a window there is no surface and the graphics queue
+// stands in for the present queue. A half built Context is torn down by its
+// CleanupGuard, a finished one by its Drop.
+pub struct Context {
+    pub entry: Entry,
+    pub instance: ash::Instance,
+    // negotiated with the loader, up to config.app_info.max_api_version
+    pub api_version: u32,
+    pub debug_utils_messenger: Option<vk::DebugUtilsMessengerEXT>,
+    // shares the capture the debug callback writes into
+    pub validation: ValidationInfo,
+    // the instance and messenger point at it, so it outlives both
+    pub debug_callback_data: Box<DebugCallbackData>,
+    pub surface_stuff: Option<SurfaceStuff>,
+    pub physical_device: vk::PhysicalDevice,
+    pub enabled_device_features: EnabledDeviceFeatures,
+    pub device: ash::Device,
+    pub debug_namer: DebugNamer,
+    pub queue_family: queue::QueueFamilyIndices,
+    pub graphics_queue: vk::Queue,
+    pub present_queue: vk::Queue,
+    pub command_pool: vk::CommandPool,
+    // binding 0 is the uniform buffer and binding 1 the texture, the same in every pipeline
+    pub descriptor_set_layout: vk::DescriptorSetLayout,
+    // checked against maxPushConstantsSize
+    pub push_constant_range: vk::PushConstantRange,
+    pub allocator: Allocator,
+    // the built-in triangle until Core::set_geometry replaces it
+    pub geometry: GeometryStuff,
+    // shared by every texture, set up from config.sampler
+    pub sampler: vk::Sampler,
+    // white until Core::set_texture replaces it
+    pub texture: TextureStuff,
+}
+
+impl Context {
+    pub fn build(
+        window: Option<&winit::window::Window>,
+        config: &CoreConfig,
+    ) -> RendererResult<Self> {
+        let entry = ash::Entry::new()?;
+        let debug_callback_data = DebugCallbackData::new(&config.validation);
+        // declared after the callback data so the instance is gone before it is freed
+        let mut guard = CleanupGuard::new();
+        let api_version = negotiate_api_version(&entry, config.app_info.max_api_version)?;
+        let instance = create_instance(
+            &entry,
+            window,
+            &config.app_info,
+            api_version,
+            &config.validation,
+            &debug_callback_data,
+        )?;
+        guard.push_with(&instance, |instance| unsafe { instance.destroy_instance(None) });
+        let debug_utils_messenger = setup_debug_messenger(
+            &entry,
+            &instance,
+            &config.validation,
+            &debug_callback_data,
+        )?;
+        if let Some(debug_utils_messenger) = debug_utils_messenger {
+            guard.push_with(&ext::DebugUtils::new(&entry, &instance), move |debug_utils| unsafe {
+                debug_utils.destroy_debug_utils_messenger(debug_utils_messenger, None)
+            });
+        }
+        let surface_stuff = match window {
+            Some(window) => Some(create_surface(&entry, &instance, window)?),
+            None => None,
+        };
+        let surface = surface_stuff
+            .as_ref()
+            .map_or(vk::SurfaceKHR::null(), |surface_stuff| surface_stuff.surface);
+        if let Some(surface_stuff) = &surface_stuff {
+            guard.push_with(&surface_stuff.surface_loader, move |surface_loader| unsafe {
+                surface_loader.destroy_surface(surface, None)
+            });
+        }
+        let physical_device = pick_pyhsical_device(
+            &instance,
+            surface_stuff.as_ref(),
+            &config.device_selection,
+            &config.device_requirements,
+        )?;
+        let enabled_device_features =
+            config.device_requirements.negotiate(&instance, physical_device)?;
+        let device = create_logical_device(
+            &instance,
+            &physical_device,
+            &surface,
+            &config.validation,
+            &enabled_device_features,
+        )?;
+        guard.push_with(&device, |device| unsafe { device.destroy_device(None) });
+        let debug_namer = DebugNamer::new(&entry, &instance, &device, &config.validation)?;
+        let indicies = queue::find_queue_families(&instance, &physical_device, &surface)?;
+        let graphics_queue = match indicies.graphics_family {
+            Some(graphics_family) => unsafe { device.get_device_queue(graphics_family, 0) },
+            None => return Err(RendererError::NoSuitableQueueFamily),
+        };
+        let present_queue = match indicies.present_family {
+            Some(present_family) => unsafe { device.get_device_queue(present_family, 0) },
+            None => return Err(RendererError::NoSuitableQueueFamily),
+        };
+        // queues are created with the device, dedicated transfer and compute ones only get a name
+        let transfer_queue =
+            unsafe { device.get_device_queue(indicies.transfer_family_or_graphics(), 0) };
+        let compute_queue =
+            unsafe { device.get_device_queue(indicies.compute_family_or_graphics(), 0) };
+        debug_namer.set_object_name(graphics_queue, "Graphics Queue")?;
+        if present_queue != graphics_queue {
+            debug_namer.set_object_name(present_queue, "Present Queue")?;
+        }
+        if transfer_queue != graphics_queue {
+            debug_namer.set_object_name(transfer_queue, "Transfer Queue")?;
+        }
+        if compute_queue != graphics_queue {
+            debug_namer.set_object_name(compute_queue, "Compute Queue")?;
+        }
+        let command_pool = create_command_pool(&instance, &physical_device, &device, &surface)?;
+        guard.push_with(&device, move |device| unsafe {
+            device.destroy_command_pool(command_pool, None)
+        });
+        let push_constant_range =
+            push_constant_range::<PushConstants>(vk::ShaderStageFlags::VERTEX, 0);
+        check_push_constant_ranges(&instance, physical_device, &[push_constant_range])?;
+        let descriptor_set_layout = create_descriptor_set_layout(&device)?;
+        guard.push_with(&device, move |device| unsafe {
+            device.destroy_descriptor_set_layout(descriptor_set_layout, None)
+        });
+        debug_namer.set_object_name(descriptor_set_layout, "UBO Descriptor Set Layout")?;
+        // declared after the guard, so on an early return its memory is freed
+        // before the guard destroys the device and the resources bound to it
+        let mut allocator = Allocator::new(&instance, physical_device, &device);
+        let geometry = create_geometry(
+            &device,
+            &mut allocator,
+            command_pool,
+            graphics_queue,
+            &VERTICES_DATA,
+            &INDICES_DATA,
+        )?;
+        let (vertex_buffer, index_buffer) = (geometry.vertex_buffer, geometry.index_buffer.buffer);
+        guard.push_with(&device, move |device| unsafe {
+            device.destroy_buffer(vertex_buffer, None);
+            device.destroy_buffer(index_buffer, None);
+        });
+        debug_namer.set_object_name(vertex_buffer, "Vertex Buffer")?;
+        debug_namer.set_object_name(index_buffer, "Index Buffer")?;
+        let sampler = create_sampler(
+            &instance,
+            physical_device,
+            &device,
+            &config.sampler,
+            &enabled_device_features,
+        )?;
+        guard.push_with(&device, move |device| unsafe { device.destroy_sampler(sampler, None) });
+        debug_namer.set_object_name(sampler, "Texture Sampler")?;
+        let texture = create_white_texture(
+            &instance,
+            physical_device,
+            &device,
+            &mut allocator,
+            command_pool,
+            graphics_queue,
+        )?;
+        let (texture_image, texture_image_view) = (texture.image, texture.image_view);
+        guard.push_with(&device, move |device| unsafe {
+            device.destroy_image_view(texture_image_view, None);
+            device.destroy_image(texture_image, None);
+        });
+        debug_namer.set_object_name(texture.image, "Texture Image")?;
+        debug_namer.set_object_name(texture.image_view, "Texture Image View")?;
+
+        guard.disarm();
+        Ok(Context {
+            entry,
+            instance,
+            api_version,
+            debug_utils_messenger,
+            validation: config.validation.clone(),
+            debug_callback_data,
+            surface_stuff,
+            physical_device,
+            enabled_device_features,
+            device,
+            debug_namer,
+            queue_family: indicies,
+            graphics_queue,
+            present_queue,
+            command_pool,
+            descriptor_set_layout,
+            push_constant_range,
+            allocator,
+            geometry,
+            sampler,
+            texture,
+        })
+    }
+
+    // what binding 1 of every descriptor set points at
+    pub fn texture_image_info(&self) -> vk::DescriptorImageInfo {
+        vk::DescriptorImageInfo {
+            sampler: self.sampler,
+            image_view: self.texture.image_view,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        }
+    }
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        unsafe {
+            destroy_geometry(&mut self.allocator, &self.geometry);
+            destroy_texture(&self.device, &mut self.allocator, &self.texture);
+            self.allocator.destroy();
+            self.device.destroy_sampler(self.sampler, None);
+            self.device
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+            self.device.destroy_command_pool(self.command_pool, None);
+            self.device.destroy_device(None);
+            if let Some(surface_stuff) = &self.surface_stuff {
+                surface_stuff
+                    .surface_loader
+                    .destroy_surface(surface_stuff.surface, None);
+            }
+            destroy_debug_messenger(
+                &self.entry,
+                &self.instance,
+                self.debug_utils_messenger,
+                &self.debug_callback_data,
+            );
+
+            self.instance.destroy_instance(None);
+        }
+    }
+}
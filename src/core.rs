@@ -1,20 +1,30 @@
 use ash::version::DeviceV1_0;
-use winit;
 
-use crate::command::{create_command_buffers, create_command_pool};
-use crate::debug::{destroy_debug_messenger, setup_debug_messenger};
+use crate::buffer::{create_geometry, destroy_geometry, GeometryStuff};
+use crate::cleanup::CleanupGuard;
+use crate::command::{create_command_buffers, DrawStuff};
+use crate::config::CoreConfig;
+use crate::context::Context;
+use crate::descriptor::update_texture_descriptors;
+use crate::device_features::EnabledDeviceFeatures;
+use crate::error::{RendererError, RendererResult, VkResultExt};
 use crate::framebuffer::create_framebuffer;
-use crate::instance::create_instance;
-use crate::logical_device::create_logical_device;
-use crate::physical_device::pick_pyhsical_device;
+use crate::model::load_obj_model;
 use crate::pipeline::{create_graphic_pipeline, create_render_pass};
-use crate::queue;
-use crate::surface::create_surface;
+use crate::push_constant::PushConstants;
+use crate::queue::QueueFamilyIndices;
 use crate::swapchain::{create_image_views, create_swapchain};
-use ash::version::InstanceV1_0;
+use crate::texture::{destroy_texture, load_texture, TextureStuff};
+use crate::uniform::{
+    create_uniform_stuff, default_view, destroy_uniform_stuff, update_uniform_buffer,
+    UniformBufferObject, UniformStuff,
+};
 use ash::vk;
-use ash::Entry;
+use cgmath::{Deg, Matrix4};
+use std::mem;
+use std::path::Path;
 use std::ptr;
+use std::time::Instant;
 const MAX_FRAMES_IN_FLIGHT: usize = 2;
 struct SyncObjects {
     image_available_semaphores: Vec<vk::Semaphore>,
@@ -28,19 +38,11 @@ struct SyncObjects {
 // }
 
 pub struct Core {
+    // dropped before the window, the surface must not outlive it
+    context: Context,
     window: winit::window::Window,
-    entry: Entry,
-    instance: ash::Instance,
-    surface: ash::vk::SurfaceKHR,
-    surface_loader: ash::extensions::khr::Surface,
-    debug_utils_messenger: Option<vk::DebugUtilsMessengerEXT>,
-    physical_device: vk::PhysicalDevice,
-    device: ash::Device,
-    graphics_queue: vk::Queue,
-    present_queue: vk::Queue,
     swap_chain: vk::SwapchainKHR,
     swap_chain_loader: ash::extensions::khr::Swapchain,
-    swap_chain_images: Vec<vk::Image>,
     swap_chain_image_format: vk::Format,
     swap_chain_extent: vk::Extent2D,
     swap_chain_image_views: Vec<vk::ImageView>,
@@ -48,116 +50,192 @@ pub struct Core {
     pipeline_layout: vk::PipelineLayout,
     render_pass: vk::RenderPass,
     frame_buffers: Vec<vk::Framebuffer>,
-    command_pool: vk::CommandPool,
     command_buffer: Vec<vk::CommandBuffer>,
+    // one uniform buffer and descriptor set per swapchain image, indexed like the command buffers
+    uniform_stuff: UniformStuff,
+    start_time: Instant,
     image_available_semaphores: Vec<vk::Semaphore>,
     render_finished_semaphores: Vec<vk::Semaphore>,
     in_flight_fences: Vec<vk::Fence>,
     image_in_flight: Vec<vk::Fence>,
     current_frame: usize,
+    is_framebuffer_resized: bool,
 }
 
 impl Core {
     pub fn build(
         window_name: &str,
         event_loop: winit::event_loop::EventLoop<()>,
-    ) -> (Self, winit::event_loop::EventLoop<()>) {
+        config: &CoreConfig,
+    ) -> RendererResult<(Self, winit::event_loop::EventLoop<()>)> {
         // start a window
         let window = winit::window::WindowBuilder::new()
             .with_title(window_name)
             .with_inner_size(winit::dpi::LogicalSize::new(1024.0, 768.0))
-            .build(&event_loop)
-            .unwrap();
-
-        let entry = ash::Entry::new().unwrap();
-        let instance = create_instance(&entry);
-        let surface = unsafe { create_surface(&entry, &instance, &window, 1024, 768) };
-        let surface_loader = ash::extensions::khr::Surface::new(&entry, &instance);
-        let physical_device = pick_pyhsical_device(&instance, &surface);
-        let device = create_logical_device(&instance, &physical_device, &surface.surface);
-        let indicies = queue::find_queue_families(&instance, &physical_device, &surface.surface);
-        let graphics_queue = match indicies.graphics_family {
-            Some(graphics_family) => unsafe { device.get_device_queue(graphics_family, 0) },
-            None => {
-                panic!("Failed to find a suitable queue family");
-            }
-        };
-        let present_queue = match indicies.present_family {
-            Some(present_family) => unsafe { device.get_device_queue(present_family, 0) },
-            None => {
-                panic!("Failed to find a suitable queue family");
-            }
-        };
-        let debug_utils_messenger = setup_debug_messenger(&entry, &instance);
+            .build(&event_loop)?;
+
+        let mut config = config.clone();
+        if config.app_info.application_name.is_none() {
+            config.app_info.application_name = Some(window_name.to_string());
+        }
+        let mut context = Context::build(Some(&window), &config)?;
+        // declared after the context, so on an early return it runs before the
+        // context destroys the device
+        let mut guard = CleanupGuard::new();
+        let device = &context.device;
         let swapchain_stuff = create_swapchain(
-            &instance,
-            &device,
-            physical_device,
+            &context.instance,
+            device,
+            context.physical_device,
             &window,
-            &surface,
-            &indicies,
-        );
-        let render_pass = create_render_pass(&device, swapchain_stuff.swapchain_format);
+            context.surface_stuff.as_ref().unwrap(),
+            &context.queue_family,
+            vk::SwapchainKHR::null(),
+        )?;
+        let swapchain = swapchain_stuff.swapchain;
+        guard.push_with(&swapchain_stuff.swapchain_loader, move |swapchain_loader| unsafe {
+            swapchain_loader.destroy_swapchain(swapchain, None)
+        });
+        let render_pass = create_render_pass(
+            device,
+            swapchain_stuff.swapchain_format,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+        )?;
+        guard.push_with(device, move |device| unsafe {
+            device.destroy_render_pass(render_pass, None)
+        });
         let (pipeline, pipeline_layout) =
-            create_graphic_pipeline(&device, &swapchain_stuff.swapchain_extent, &render_pass);
+            create_graphic_pipeline(
+            device,
+            &render_pass,
+            &context.descriptor_set_layout,
+            &[context.push_constant_range],
+        )?;
+        guard.push_with(device, move |device| unsafe {
+            device.destroy_pipeline(pipeline, None);
+            device.destroy_pipeline_layout(pipeline_layout, None);
+        });
         let swapchain_image_views = create_image_views(
-            &device,
+            device,
             swapchain_stuff.swapchain_format,
             &swapchain_stuff.swapchain_images,
-        );
+        )?;
+        let image_views = swapchain_image_views.clone();
+        guard.push_with(device, move |device| {
+            for image_view in image_views {
+                unsafe { device.destroy_image_view(image_view, None) };
+            }
+        });
         let frame_buffers = create_framebuffer(
-            &device,
+            device,
             &swapchain_image_views,
             &render_pass,
             &swapchain_stuff.swapchain_extent,
-        );
-        let command_pool =
-            create_command_pool(&instance, &physical_device, &device, &surface.surface);
+        )?;
+        let framebuffers = frame_buffers.clone();
+        guard.push_with(device, move |device| {
+            for framebuffer in framebuffers {
+                unsafe { device.destroy_framebuffer(framebuffer, None) };
+            }
+        });
+        let texture_info = context.texture_image_info();
+        let uniform_stuff = create_uniform_stuff(
+            device,
+            &mut context.allocator,
+            &context.descriptor_set_layout,
+            swapchain_stuff.swapchain_images.len(),
+            &texture_info,
+        )?;
+        // the context's allocator frees their memory
+        let descriptor_pool = uniform_stuff.descriptor_pool;
+        let uniform_buffers: Vec<vk::Buffer> =
+            uniform_stuff.uniform_buffers.iter().map(|(buffer, _)| *buffer).collect();
+        guard.push_with(device, move |device| unsafe {
+            device.destroy_descriptor_pool(descriptor_pool, None);
+            for uniform_buffer in uniform_buffers {
+                device.destroy_buffer(uniform_buffer, None);
+            }
+        });
+        // freed along with the command pool if a later step fails
         let command_buffer = create_command_buffers(
-            &device,
-            &command_pool,
-            &pipeline,
+            device,
+            &context.command_pool,
             &frame_buffers,
             &render_pass,
             &swapchain_stuff.swapchain_extent,
-        );
-        let sync_ojbects = Core::create_sync_objects(&device);
-
-        (
-            Core {
-                image_available_semaphores: sync_ojbects.image_available_semaphores,
-                render_finished_semaphores: sync_ojbects.render_finished_semaphores,
-                in_flight_fences: sync_ojbects.inflight_fences,
-                image_in_flight: vec![vk::Fence::null(); swapchain_stuff.swapchain_images.len()],
-                window,
-                entry,
-                instance,
-                surface: surface.surface,
-                surface_loader: surface.surface_loader,
-                debug_utils_messenger,
-                physical_device,
-                device,
-                graphics_queue,
-                present_queue,
-                swap_chain: swapchain_stuff.swapchain,
-                swap_chain_loader: swapchain_stuff.swapchain_loader,
-                swap_chain_images: swapchain_stuff.swapchain_images,
-                swap_chain_image_format: swapchain_stuff.swapchain_format,
-                swap_chain_extent: swapchain_stuff.swapchain_extent,
-                swap_chain_image_views: swapchain_image_views,
-                pipeline,
+            &DrawStuff {
+                graphics_pipeline: pipeline,
                 pipeline_layout,
-                render_pass,
-                frame_buffers,
-                command_buffer,
-                command_pool,
-                current_frame: 0,
+                descriptor_sets: &uniform_stuff.descriptor_sets,
+                push_constant_range: context.push_constant_range,
+                push_constants: PushConstants::default(),
+                vertex_buffer: context.geometry.vertex_buffer,
+                index_buffer: context.geometry.index_buffer.buffer,
+                index_type: context.geometry.index_buffer.index_type,
+                index_count: context.geometry.index_buffer.index_count,
             },
-            event_loop,
-        )
+            &context.debug_namer,
+        )?;
+        let sync_ojbects = Core::create_sync_objects(device, &mut guard)?;
+        let debug_namer = &context.debug_namer;
+        debug_namer.set_object_names(
+            &sync_ojbects.image_available_semaphores,
+            "Image Available Semaphore",
+        )?;
+        debug_namer.set_object_names(
+            &sync_ojbects.render_finished_semaphores,
+            "Render Finished Semaphore",
+        )?;
+        debug_namer.set_object_names(&sync_ojbects.inflight_fences, "In Flight Fence")?;
+
+        guard.disarm();
+        let core = Core {
+            image_available_semaphores: sync_ojbects.image_available_semaphores,
+            render_finished_semaphores: sync_ojbects.render_finished_semaphores,
+            in_flight_fences: sync_ojbects.inflight_fences,
+            image_in_flight: vec![vk::Fence::null(); swapchain_stuff.swapchain_images.len()],
+            context,
+            window,
+            swap_chain: swapchain_stuff.swapchain,
+            swap_chain_loader: swapchain_stuff.swapchain_loader,
+            swap_chain_image_format: swapchain_stuff.swapchain_format,
+            swap_chain_extent: swapchain_stuff.swapchain_extent,
+            swap_chain_image_views: swapchain_image_views,
+            pipeline,
+            pipeline_layout,
+            render_pass,
+            frame_buffers,
+            command_buffer,
+            uniform_stuff,
+            start_time: Instant::now(),
+            current_frame: 0,
+            is_framebuffer_resized: false,
+        };
+        core.name_swapchain_objects(&swapchain_stuff.swapchain_images)?;
+        Ok((core, event_loop))
+    }
+
+    // Everything named here may be rebuilt by recreate_swapchain and is named again.
+    fn name_swapchain_objects(&self, swapchain_images: &[vk::Image]) -> RendererResult<()> {
+        let debug_namer = &self.context.debug_namer;
+        debug_namer.set_object_name(self.swap_chain, "Swapchain")?;
+        debug_namer.set_object_names(swapchain_images, "Swapchain Image")?;
+        debug_namer.set_object_names(&self.swap_chain_image_views, "Swapchain Image View")?;
+        debug_namer.set_object_name(self.render_pass, "Main Render Pass")?;
+        debug_namer.set_object_name(self.pipeline, "Graphics Pipeline")?;
+        debug_namer.set_object_name(self.pipeline_layout, "Graphics Pipeline Layout")?;
+        debug_namer.set_object_names(&self.frame_buffers, "Framebuffer")?;
+        let uniform_buffers: Vec<vk::Buffer> =
+            self.uniform_stuff.uniform_buffers.iter().map(|(buffer, _)| *buffer).collect();
+        debug_namer.set_object_names(&uniform_buffers, "Uniform Buffer")?;
+        debug_namer.set_object_name(self.uniform_stuff.descriptor_pool, "UBO Descriptor Pool")?;
+        debug_namer.set_object_names(&self.uniform_stuff.descriptor_sets, "UBO Descriptor Set")
     }
 
-    fn create_sync_objects(device: &ash::Device) -> SyncObjects {
+    fn create_sync_objects(
+        device: &ash::Device,
+        guard: &mut CleanupGuard,
+    ) -> RendererResult<SyncObjects> {
         let mut sync_objects = SyncObjects {
             image_available_semaphores: vec![],
             render_finished_semaphores: vec![],
@@ -180,13 +258,20 @@ impl Core {
             unsafe {
                 let image_available_semaphore = device
                     .create_semaphore(&semaphore_create_info, None)
-                    .expect("Failed to create Semaphore Object!");
+                    .context("Failed to create Semaphore Object!")?;
+                guard.push_with(device, move |device| {
+                    device.destroy_semaphore(image_available_semaphore, None)
+                });
                 let render_finished_semaphore = device
                     .create_semaphore(&semaphore_create_info, None)
-                    .expect("Failed to create Semaphore Object!");
+                    .context("Failed to create Semaphore Object!")?;
+                guard.push_with(device, move |device| {
+                    device.destroy_semaphore(render_finished_semaphore, None)
+                });
                 let inflight_fence = device
                     .create_fence(&fence_create_info, None)
-                    .expect("Failed to create Fence Object!");
+                    .context("Failed to create Fence Object!")?;
+                guard.push_with(device, move |device| device.destroy_fence(inflight_fence, None));
 
                 sync_objects
                     .image_available_semaphores
@@ -198,34 +283,48 @@ impl Core {
             }
         }
 
-        sync_objects
+        Ok(sync_objects)
+    }
+    // A minimized window reports a 0x0 framebuffer, which is not a valid swapchain extent.
+    fn is_minimized(&self) -> bool {
+        let window_size = self.window.inner_size();
+        window_size.width == 0 || window_size.height == 0
     }
-    fn burn_frame(&mut self) {
+
+    fn burn_frame(&mut self) -> RendererResult<()> {
+        if self.is_minimized() {
+            return Ok(());
+        }
         let wait_fences = [self.in_flight_fences[self.current_frame]];
 
-        let (image_index, _is_sub_optimal) = unsafe {
-            self.device
-                .wait_for_fences(&wait_fences, true, std::u64::MAX)
-                .expect("Failed to wait for Fence!");
-            let ok = self
-                .swap_chain_loader
-                .acquire_next_image(
-                    self.swap_chain,
-                    std::u64::MAX,
-                    self.image_available_semaphores[self.current_frame],
-                    vk::Fence::null(),
-                )
-                .expect("Failed to acquire next image.");
-            ok
+        let (image_index, is_acquire_sub_optimal) = unsafe {
+            self.context.device
+                .wait_for_fences(&wait_fences, true, u64::MAX)
+                .context("Failed to wait for Fence!")?;
+            let result = self.swap_chain_loader.acquire_next_image(
+                self.swap_chain,
+                u64::MAX,
+                self.image_available_semaphores[self.current_frame],
+                vk::Fence::null(),
+            );
+            match result {
+                Ok(image_index) => image_index,
+                Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => return self.recreate_swapchain(),
+                Err(result) => {
+                    return Err(RendererError::from_vk("Failed to acquire next image.", result))
+                }
+            }
         };
-        if (self.image_in_flight[image_index as usize] != vk::Fence::null()) {
+        if self.image_in_flight[image_index as usize] != vk::Fence::null() {
             unsafe {
-                self.device
-                    .wait_for_fences(&wait_fences, true, std::u64::MAX)
-                    .expect("Failed to wait for Fence!");
+                self.context.device
+                    .wait_for_fences(&[self.image_in_flight[image_index as usize]], true, u64::MAX)
+                    .context("Failed to wait for Fence!")?;
             }
         }
         self.image_in_flight[image_index as usize] = self.in_flight_fences[self.current_frame];
+        // nothing in flight reads this image's uniform buffer anymore
+        self.update_uniform_buffer(image_index as usize);
         let wait_semaphores = [self.image_available_semaphores[self.current_frame]];
         let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
         let signal_semaphores = [self.render_finished_semaphores[self.current_frame]];
@@ -242,15 +341,17 @@ impl Core {
             ..Default::default()
         }];
         unsafe {
-            self.device
+            self.context.device
                 .reset_fences(&[self.in_flight_fences[self.current_frame]])
-                .expect("Failed to reset Fence!");
+                .context("Failed to reset Fence!")?;
 
-            let ok = self.device.queue_submit(
-                self.graphics_queue,
-                &submit_infos,
-                self.in_flight_fences[self.current_frame],
-            );
+            self.context.device
+                .queue_submit(
+                    self.context.graphics_queue,
+                    &submit_infos,
+                    self.in_flight_fences[self.current_frame],
+                )
+                .context("Failed to execute queue submit.")?;
         }
 
         let swapchains = [self.swap_chain];
@@ -263,13 +364,310 @@ impl Core {
             p_image_indices: &image_index,
             ..Default::default()
         };
+        let result = unsafe {
+            self.swap_chain_loader
+                .queue_present(self.context.present_queue, &present_info)
+        };
+        // ash reports SUBOPTIMAL_KHR as Ok(true), from both acquire and present
+        let is_resized = match result {
+            Ok(is_sub_optimal) => is_sub_optimal || is_acquire_sub_optimal,
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => true,
+            Err(result) => {
+                return Err(RendererError::from_vk("Failed to execute queue present.", result))
+            }
+        };
+        self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+        if is_resized || self.is_framebuffer_resized {
+            self.is_framebuffer_resized = false;
+            self.recreate_swapchain()?;
+        }
+        self.context.validation.check_errors()
+    }
+
+    // spins the model around Z at 90 degrees per second
+    fn update_uniform_buffer(&self, image_index: usize) {
+        let elapsed = self.start_time.elapsed().as_secs_f32();
+        let ubo = UniformBufferObject::new(
+            Matrix4::from_angle_z(Deg(elapsed * 90.0)),
+            default_view(),
+            self.swap_chain_extent,
+        );
+        update_uniform_buffer(&self.uniform_stuff.uniform_buffers[image_index].1, &ubo);
+    }
+
+    // Rebuilds what depends on the swapchain images and extent, handing the current
+    // swapchain to the driver as old_swapchain so it can reuse resources. The
+    // viewport is dynamic, so the pipeline only follows a change of format, and the
+    // uniform buffers and descriptor sets only a change of image count.
+    fn recreate_swapchain(&mut self) -> RendererResult<()> {
+        if self.is_minimized() {
+            // retry once the window has been restored
+            self.is_framebuffer_resized = true;
+            return Ok(());
+        }
         unsafe {
-            let ok = self
-                .swap_chain_loader
-                .queue_present(self.present_queue, &present_info)
-                .expect("Failed to execute queue present.");
+            self.context
+                .device
+                .device_wait_idle()
+                .context("Failed to wait device idle.")?;
         }
-        self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+        self.cleanup_swapchain();
+
+        let device = &self.context.device;
+        let old_swapchain = self.swap_chain;
+        let swapchain_stuff = create_swapchain(
+            &self.context.instance,
+            device,
+            self.context.physical_device,
+            &self.window,
+            self.context.surface_stuff.as_ref().unwrap(),
+            &self.context.queue_family,
+            old_swapchain,
+        )?;
+        unsafe {
+            self.swap_chain_loader.destroy_swapchain(old_swapchain, None);
+        }
+        self.swap_chain_loader = swapchain_stuff.swapchain_loader;
+        self.swap_chain = swapchain_stuff.swapchain;
+        self.swap_chain_extent = swapchain_stuff.swapchain_extent;
+
+        if swapchain_stuff.swapchain_format != self.swap_chain_image_format {
+            // nulled first, so Drop doesn't destroy them again if the rebuild fails
+            unsafe {
+                device.destroy_pipeline(self.pipeline, None);
+                device.destroy_pipeline_layout(self.pipeline_layout, None);
+                device.destroy_render_pass(self.render_pass, None);
+            }
+            self.pipeline = vk::Pipeline::null();
+            self.pipeline_layout = vk::PipelineLayout::null();
+            self.render_pass = vk::RenderPass::null();
+            self.swap_chain_image_format = swapchain_stuff.swapchain_format;
+            self.render_pass = create_render_pass(
+                device,
+                self.swap_chain_image_format,
+                vk::ImageLayout::PRESENT_SRC_KHR,
+            )?;
+            let (pipeline, pipeline_layout) = create_graphic_pipeline(
+                device,
+                &self.render_pass,
+                &self.context.descriptor_set_layout,
+                &[self.context.push_constant_range],
+            )?;
+            self.pipeline = pipeline;
+            self.pipeline_layout = pipeline_layout;
+        }
+        self.swap_chain_image_views = create_image_views(
+            device,
+            self.swap_chain_image_format,
+            &swapchain_stuff.swapchain_images,
+        )?;
+        self.frame_buffers = create_framebuffer(
+            device,
+            &self.swap_chain_image_views,
+            &self.render_pass,
+            &self.swap_chain_extent,
+        )?;
+        let image_count = swapchain_stuff.swapchain_images.len();
+        if image_count != self.uniform_stuff.uniform_buffers.len() {
+            let texture_info = self.context.texture_image_info();
+            destroy_uniform_stuff(device, &mut self.context.allocator, &mut self.uniform_stuff);
+            self.uniform_stuff = create_uniform_stuff(
+                device,
+                &mut self.context.allocator,
+                &self.context.descriptor_set_layout,
+                image_count,
+                &texture_info,
+            )?;
+        }
+        self.command_buffer = self.record_command_buffers()?;
+        self.image_in_flight = vec![vk::Fence::null(); self.swap_chain_image_views.len()];
+        self.name_swapchain_objects(&swapchain_stuff.swapchain_images)
+    }
+
+    // Records one command buffer per framebuffer with the current pipeline and geometry.
+    fn record_command_buffers(&self) -> RendererResult<Vec<vk::CommandBuffer>> {
+        create_command_buffers(
+            &self.context.device,
+            &self.context.command_pool,
+            &self.frame_buffers,
+            &self.render_pass,
+            &self.swap_chain_extent,
+            &DrawStuff {
+                graphics_pipeline: self.pipeline,
+                pipeline_layout: self.pipeline_layout,
+                descriptor_sets: &self.uniform_stuff.descriptor_sets,
+                push_constant_range: self.context.push_constant_range,
+                push_constants: PushConstants::default(),
+                vertex_buffer: self.context.geometry.vertex_buffer,
+                index_buffer: self.context.geometry.index_buffer.buffer,
+                index_type: self.context.geometry.index_buffer.index_type,
+                index_count: self.context.geometry.index_buffer.index_count,
+            },
+            &self.context.debug_namer,
+        )
+    }
+
+    // Replaces the texture every descriptor set samples. On failure the old texture
+    // stays bound and drawn.
+    pub fn set_texture(&mut self, path: &Path) -> RendererResult<()> {
+        let texture = self.load_texture_file(path)?;
+        self.replace_draw_resources(Some(texture), None)
+    }
+
+    // Draws an OBJ model instead of the current geometry, along with its diffuse texture.
+    // Both are uploaded before anything is swapped, so the command buffers are recorded once
+    // and a failure leaves the old model drawn.
+    pub fn load_model(&mut self, path: &Path) -> RendererResult<()> {
+        let mesh = load_obj_model(path)?;
+        let texture = match &mesh.diffuse_texture {
+            Some(texture_path) => Some(self.load_texture_file(texture_path)?),
+            None => None,
+        };
+        let geometry = create_geometry(
+            &self.context.device,
+            &mut self.context.allocator,
+            self.context.command_pool,
+            self.context.graphics_queue,
+            &mesh.vertices,
+            &mesh.indices,
+        );
+        match geometry {
+            Ok(geometry) => self.replace_draw_resources(texture, Some(geometry)),
+            Err(err) => {
+                if let Some(texture) = &texture {
+                    destroy_texture(&self.context.device, &mut self.context.allocator, texture);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    fn load_texture_file(&mut self, path: &Path) -> RendererResult<TextureStuff> {
+        load_texture(
+            &self.context.instance,
+            self.context.physical_device,
+            &self.context.device,
+            &mut self.context.allocator,
+            self.context.command_pool,
+            self.context.graphics_queue,
+            path,
+        )
+    }
+
+    // Swaps in whichever of `texture` and `geometry` is given, points the descriptor sets
+    // at the new texture and records the command buffers again. The old resources are
+    // destroyed only once that worked, otherwise they are put back and the new ones go.
+    fn replace_draw_resources(
+        &mut self,
+        texture: Option<TextureStuff>,
+        geometry: Option<GeometryStuff>,
+    ) -> RendererResult<()> {
+        // the descriptor sets and buffers are replaced below, none of them may be in use
+        let wait_result = unsafe {
+            self.context
+                .device
+                .device_wait_idle()
+                .context("Failed to wait device idle.")
+        };
+        if let Err(err) = wait_result {
+            self.destroy_draw_resources(texture, geometry);
+            return Err(err);
+        }
+        let old_texture = texture.map(|texture| self.swap_texture(texture));
+        let old_geometry =
+            geometry.map(|geometry| mem::replace(&mut self.context.geometry, geometry));
+        match self.record_command_buffers() {
+            Ok(command_buffers) => {
+                self.free_command_buffers();
+                self.command_buffer = command_buffers;
+                self.destroy_draw_resources(old_texture, old_geometry);
+                self.name_draw_resources()
+            }
+            Err(err) => {
+                let texture = old_texture.map(|texture| self.swap_texture(texture));
+                let geometry =
+                    old_geometry.map(|geometry| mem::replace(&mut self.context.geometry, geometry));
+                self.destroy_draw_resources(texture, geometry);
+                Err(err)
+            }
+        }
+    }
+
+    // Returns the texture that was bound, the descriptor sets must not be in use.
+    fn swap_texture(&mut self, texture: TextureStuff) -> TextureStuff {
+        let old_texture = mem::replace(&mut self.context.texture, texture);
+        update_texture_descriptors(
+            &self.context.device,
+            &self.uniform_stuff.descriptor_sets,
+            &self.context.texture_image_info(),
+        );
+        old_texture
+    }
+
+    fn destroy_draw_resources(
+        &mut self,
+        texture: Option<TextureStuff>,
+        geometry: Option<GeometryStuff>,
+    ) {
+        let allocator = &mut self.context.allocator;
+        if let Some(texture) = &texture {
+            destroy_texture(&self.context.device, allocator, texture);
+        }
+        if let Some(geometry) = &geometry {
+            destroy_geometry(allocator, geometry);
+        }
+    }
+
+    fn name_draw_resources(&self) -> RendererResult<()> {
+        let debug_namer = &self.context.debug_namer;
+        debug_namer.set_object_name(self.context.texture.image, "Texture Image")?;
+        debug_namer.set_object_name(self.context.texture.image_view, "Texture Image View")?;
+        debug_namer.set_object_name(self.context.geometry.vertex_buffer, "Vertex Buffer")?;
+        debug_namer.set_object_name(self.context.geometry.index_buffer.buffer, "Index Buffer")
+    }
+
+    // Destroys the objects tied to the swapchain images, the swapchain itself is
+    // left alive so it can be passed as old_swapchain. The vecs are drained as
+    // they go, so Drop can call this again after recreate_swapchain failed half way.
+    fn cleanup_swapchain(&mut self) {
+        self.free_command_buffers();
+        let device = &self.context.device;
+        unsafe {
+            for framebuffer in self.frame_buffers.drain(..) {
+                device.destroy_framebuffer(framebuffer, None);
+            }
+            for view in self.swap_chain_image_views.drain(..) {
+                device.destroy_image_view(view, None);
+            }
+        }
+    }
+
+    // Left empty until the command buffers are recorded again, so a failed
+    // re-record doesn't free them twice.
+    fn free_command_buffers(&mut self) {
+        if !self.command_buffer.is_empty() {
+            unsafe {
+                self.context
+                    .device
+                    .free_command_buffers(self.context.command_pool, &self.command_buffer);
+            }
+        }
+        self.command_buffer.clear();
+    }
+
+    // the instance API version negotiated with the loader
+    pub fn api_version(&self) -> u32 {
+        self.context.api_version
+    }
+
+    // the transfer and compute families are only set when the device has dedicated ones
+    pub fn queue_family_indices(&self) -> &QueueFamilyIndices {
+        &self.context.queue_family
+    }
+
+    // the required device features and extensions plus the optional ones that were available
+    pub fn enabled_device_features(&self) -> &EnabledDeviceFeatures {
+        &self.context.enabled_device_features
     }
 
     pub fn burn((mut core, event_loop): (Self, winit::event_loop::EventLoop<()>)) {
@@ -277,42 +675,48 @@ impl Core {
             // handle event
             match event {
                 winit::event::Event::WindowEvent { event, .. } => match event {
+                    winit::event::WindowEvent::Resized(_) => {
+                        core.is_framebuffer_resized = true;
+                    }
                     winit::event::WindowEvent::CloseRequested => {
-                        unsafe {
-                            core.device
-                                .device_wait_idle()
-                                .expect("Failed to wait device idle.");
-                        };
+                        if let Err(result) = unsafe { core.context.device.device_wait_idle() } {
+                            log::error!("Failed to wait device idle: {}", result);
+                        }
+                        *control_flow = winit::event_loop::ControlFlow::Exit;
+                    }
+                    winit::event::WindowEvent::KeyboardInput {
+                        input:
+                            winit::event::KeyboardInput {
+                                virtual_keycode: Some(winit::event::VirtualKeyCode::Escape),
+                                state: winit::event::ElementState::Pressed,
+                                ..
+                            },
+                        ..
+                    } => {
+                        if let Err(result) = unsafe { core.context.device.device_wait_idle() } {
+                            log::error!("Failed to wait device idle: {}", result);
+                        }
                         *control_flow = winit::event_loop::ControlFlow::Exit;
                     }
-                    winit::event::WindowEvent::KeyboardInput { input, .. } => match input {
-                        winit::event::KeyboardInput {
-                            virtual_keycode,
-                            state,
-                            ..
-                        } => match (virtual_keycode, state) {
-                            (
-                                Some(winit::event::VirtualKeyCode::Escape),
-                                winit::event::ElementState::Pressed,
-                            ) => {
-                                dbg!();
-                                unsafe {
-                                    core.device
-                                        .device_wait_idle()
-                                        .expect("Failed to wait device idle.");
-                                };
-                                *control_flow = winit::event_loop::ControlFlow::Exit;
-                            }
-                            _ => {}
-                        },
-                    },
                     _ => {}
                 },
                 winit::event::Event::MainEventsCleared => {
-                    core.window.request_redraw();
+                    if *control_flow == winit::event_loop::ControlFlow::Exit {
+                        return;
+                    }
+                    if core.is_minimized() {
+                        // sleep until the next window event instead of spinning
+                        *control_flow = winit::event_loop::ControlFlow::Wait;
+                    } else {
+                        *control_flow = winit::event_loop::ControlFlow::Poll;
+                        core.window.request_redraw();
+                    }
                 }
                 winit::event::Event::RedrawRequested(_window_id) => {
-                    core.burn_frame();
+                    if let Err(err) = core.burn_frame() {
+                        log::error!("{}", err);
+                        *control_flow = winit::event_loop::ControlFlow::Exit;
+                    }
                 }
                 _ => (),
             }
@@ -323,32 +727,31 @@ impl Core {
 
 impl Drop for Core {
     fn drop(&mut self) {
+        // nothing can be destroyed while the GPU still uses it, and there is no
+        // one left to report a failure to
+        let _ = unsafe { self.context.device.device_wait_idle() };
         unsafe {
             for i in 0..MAX_FRAMES_IN_FLIGHT {
-                self.device
+                self.context.device
                     .destroy_semaphore(self.image_available_semaphores[i], None);
-                self.device
+                self.context.device
                     .destroy_semaphore(self.render_finished_semaphores[i], None);
-                self.device.destroy_fence(self.in_flight_fences[i], None);
-            }
-            self.device.destroy_command_pool(self.command_pool, None);
-            for framebuffer in self.frame_buffers.iter() {
-                self.device.destroy_framebuffer(*framebuffer, None);
-            }
-            for view in self.swap_chain_image_views.iter() {
-                self.device.destroy_image_view(*view, None);
+                self.context.device.destroy_fence(self.in_flight_fences[i], None);
             }
+        }
+        self.cleanup_swapchain();
+        destroy_uniform_stuff(
+            &self.context.device,
+            &mut self.context.allocator,
+            &mut self.uniform_stuff,
+        );
+        unsafe {
             self.swap_chain_loader
                 .destroy_swapchain(self.swap_chain, None);
-            self.device
+            self.context.device
                 .destroy_pipeline_layout(self.pipeline_layout, None);
-            self.device.destroy_render_pass(self.render_pass, None);
-            self.device.destroy_pipeline(self.pipeline, None);
-            self.device.destroy_device(None);
-            self.surface_loader.destroy_surface(self.surface, None);
-            destroy_debug_messenger(&self.entry, &self.instance, self.debug_utils_messenger);
-
-            self.instance.destroy_instance(None);
+            self.context.device.destroy_render_pass(self.render_pass, None);
+            self.context.device.destroy_pipeline(self.pipeline, None);
         }
     }
 }
@@ -1,19 +1,392 @@
 use ash::extensions::ext;
 use ash::version::EntryV1_0;
 use ash::vk;
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
+use std::fmt;
 use std::os::raw::{c_char, c_void};
-use std::ptr;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
+use crate::error::{RendererError, RendererResult, VkResultExt};
+use crate::instance::is_instance_extension_available;
+
+#[derive(Debug, Clone)]
 pub struct ValidationInfo {
     pub is_enable: bool,
-    pub required_validation_layers: [&'static str; 1],
+    pub required_validation_layers: Vec<String>,
+    pub message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    pub message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    // records every message the callback receives, e.g. for test assertions
+    pub capture: Option<ValidationCapture>,
+    // fail the frame when the capture holds an ERROR severity message
+    pub is_fail_fast: bool,
+    // known-benign messages that are neither logged nor captured
+    pub suppressed_message_ids: Vec<MessageId>,
+    // chained into instance creation through VK_EXT_validation_features
+    pub enabled_features: Vec<ValidationFeature>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValidationFeature {
+    GpuAssisted,
+    BestPractices,
+    // routes debugPrintfEXT output from shaders through debug_utils_callback
+    DebugPrintf,
+    SynchronizationValidation,
+}
+
+impl ValidationFeature {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "gpu_assisted" | "gpu" => Some(ValidationFeature::GpuAssisted),
+            "best_practices" => Some(ValidationFeature::BestPractices),
+            "debug_printf" | "printf" => Some(ValidationFeature::DebugPrintf),
+            "synchronization" | "sync" => Some(ValidationFeature::SynchronizationValidation),
+            _ => None,
+        }
+    }
+
+    // ash predates the newer enumerants, so the raw values come from the Vulkan registry.
+    pub fn to_vk(self) -> vk::ValidationFeatureEnableEXT {
+        match self {
+            ValidationFeature::GpuAssisted => vk::ValidationFeatureEnableEXT::GPU_ASSISTED,
+            ValidationFeature::BestPractices => vk::ValidationFeatureEnableEXT::from_raw(2),
+            ValidationFeature::DebugPrintf => vk::ValidationFeatureEnableEXT::from_raw(3),
+            ValidationFeature::SynchronizationValidation => {
+                vk::ValidationFeatureEnableEXT::from_raw(4)
+            }
+        }
+    }
 }
 
-pub const VALIDATION: ValidationInfo = ValidationInfo {
-    is_enable: true,
-    required_validation_layers: ["VK_LAYER_KHRONOS_validation"],
-};
+#[derive(Debug, Clone, PartialEq)]
+pub enum MessageId {
+    Number(i32),
+    Name(String),
+}
+
+impl MessageId {
+    // "0x1a2b3c4d" or a decimal number is a message ID number, anything else a pMessageIdName.
+    pub fn parse(value: &str) -> Self {
+        let value = value.trim();
+        let number = match value.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16).ok().map(|number| number as i32),
+            None => value.parse::<i32>().ok(),
+        };
+        match number {
+            Some(number) => MessageId::Number(number),
+            None => MessageId::Name(value.to_string()),
+        }
+    }
+
+    fn matches(&self, message_id_name: &str, message_id_number: i32) -> bool {
+        match self {
+            MessageId::Number(number) => *number == message_id_number,
+            MessageId::Name(name) => name == message_id_name,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CapturedMessage {
+    pub severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    pub message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    pub message_id_name: String,
+    pub message_id_number: i32,
+    pub message: String,
+}
+
+// the same layout the callback logs with
+impl fmt::Display for CapturedMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {} (0x{:x}) {}",
+            message_type_name(self.message_type),
+            self.message_id_name,
+            self.message_id_number,
+            self.message
+        )
+    }
+}
+
+// Thread-safe message buffer shared between the debug callback and the application.
+#[derive(Debug, Clone, Default)]
+pub struct ValidationCapture {
+    messages: Arc<Mutex<Vec<CapturedMessage>>>,
+}
+
+impl ValidationCapture {
+    pub fn new() -> Self {
+        ValidationCapture::default()
+    }
+
+    // Empties the buffer, so every message is handed out once.
+    pub fn take(&self) -> Vec<CapturedMessage> {
+        match self.messages.lock() {
+            Ok(mut messages) => messages.drain(..).collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    fn push(&self, message: CapturedMessage) {
+        if let Ok(mut messages) = self.messages.lock() {
+            messages.push(message);
+        }
+    }
+}
+
+// Reached by debug_utils_callback through p_user_data. Core and HeadlessCore keep it
+// boxed until the instance is destroyed, since the instance create info points at it too.
+pub struct DebugCallbackData {
+    capture: Option<ValidationCapture>,
+    suppressed_message_ids: Vec<MessageId>,
+    suppressed_counts: Mutex<HashMap<String, usize>>,
+}
+
+impl DebugCallbackData {
+    pub fn new(validation: &ValidationInfo) -> Box<Self> {
+        Box::new(DebugCallbackData {
+            capture: validation.capture.clone(),
+            suppressed_message_ids: validation.suppressed_message_ids.clone(),
+            suppressed_counts: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn is_suppressed(&self, message_id_name: &str, message_id_number: i32) -> bool {
+        let is_suppressed = self
+            .suppressed_message_ids
+            .iter()
+            .any(|id| id.matches(message_id_name, message_id_number));
+        if is_suppressed {
+            let key = if message_id_name.is_empty() {
+                format!("0x{:x}", message_id_number)
+            } else {
+                message_id_name.to_string()
+            };
+            if let Ok(mut suppressed_counts) = self.suppressed_counts.lock() {
+                *suppressed_counts.entry(key).or_insert(0) += 1;
+            }
+        }
+        is_suppressed
+    }
+
+    fn as_user_data(&self) -> *mut c_void {
+        self as *const DebugCallbackData as *mut c_void
+    }
+
+    // Suppressed messages are known to be benign, but a count that keeps growing
+    // is still worth a look, so the summary goes out as a warning.
+    fn log_suppressed_summary(&self) {
+        let suppressed_counts = match self.suppressed_counts.lock() {
+            Ok(suppressed_counts) => suppressed_counts,
+            Err(_) => return,
+        };
+        let total: usize = suppressed_counts.values().sum();
+        if total == 0 {
+            return;
+        }
+        log::warn!(target: "vulkan", "Suppressed {} validation message(s):", total);
+        let mut suppressed_counts: Vec<(&String, &usize)> = suppressed_counts.iter().collect();
+        suppressed_counts.sort();
+        for (message_id, count) in suppressed_counts {
+            log::warn!(target: "vulkan", "\t{}: {}", message_id, count);
+        }
+    }
+}
+
+impl Default for ValidationInfo {
+    // Validation is on for debug builds only, so release builds don't need the SDK layers.
+    fn default() -> Self {
+        ValidationInfo {
+            is_enable: cfg!(debug_assertions),
+            required_validation_layers: vec!["VK_LAYER_KHRONOS_validation".to_string()],
+            message_severity: vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
+                | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING,
+            message_type: vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+            capture: None,
+            is_fail_fast: false,
+            suppressed_message_ids: vec![],
+            enabled_features: vec![],
+        }
+    }
+}
+
+impl ValidationInfo {
+    // Starts from the defaults and applies, when set:
+    //   VULKAN_VALIDATION=0|1
+    //   VULKAN_VALIDATION_LAYERS=VK_LAYER_KHRONOS_validation,...
+    //   VULKAN_VALIDATION_SEVERITY=verbose,info,warning,error
+    //   VULKAN_VALIDATION_TYPES=general,validation,performance
+    //   VULKAN_VALIDATION_FAIL_FAST=1 (captures messages and fails frames on errors)
+    //   VULKAN_VALIDATION_SUPPRESS=VUID-...,0x1a2b3c4d,...
+    //   VULKAN_VALIDATION_FEATURES=best_practices,sync,debug_printf,gpu_assisted
+    pub fn from_env() -> Self {
+        let mut validation = ValidationInfo::default();
+        if let Ok(value) = std::env::var("VULKAN_VALIDATION") {
+            match value.trim().to_lowercase().as_str() {
+                "1" | "true" | "on" => validation = validation.with_enable(true),
+                "0" | "false" | "off" => validation = validation.with_enable(false),
+                _ => log::warn!("Ignoring invalid VULKAN_VALIDATION value: {}", value),
+            }
+        }
+        if let Ok(value) = std::env::var("VULKAN_VALIDATION_LAYERS") {
+            let layers: Vec<&str> = split_list(&value).collect();
+            validation = validation.with_layers(&layers);
+        }
+        if let Ok(value) = std::env::var("VULKAN_VALIDATION_SEVERITY") {
+            validation = validation.with_message_severity(parse_message_severity(&value));
+        }
+        if let Ok(value) = std::env::var("VULKAN_VALIDATION_TYPES") {
+            validation = validation.with_message_type(parse_message_type(&value));
+        }
+        if let Ok(value) = std::env::var("VULKAN_VALIDATION_FAIL_FAST") {
+            if let "1" | "true" | "on" = value.trim().to_lowercase().as_str() {
+                validation = validation
+                    .with_capture(ValidationCapture::new())
+                    .with_fail_fast(true);
+            }
+        }
+        if let Ok(value) = std::env::var("VULKAN_VALIDATION_SUPPRESS") {
+            let message_ids: Vec<&str> = split_list(&value).collect();
+            validation = validation.with_suppressed_messages(&message_ids);
+        }
+        if let Ok(value) = std::env::var("VULKAN_VALIDATION_FEATURES") {
+            let mut enabled_features = vec![];
+            for name in split_list(&value) {
+                match ValidationFeature::parse(name) {
+                    Some(feature) => enabled_features.push(feature),
+                    None => log::warn!("Ignoring unknown validation feature: {}", name),
+                }
+            }
+            validation = validation.with_features(&enabled_features);
+        }
+        validation
+    }
+
+    pub fn with_enable(mut self, is_enable: bool) -> Self {
+        self.is_enable = is_enable;
+        self
+    }
+
+    pub fn with_layers(mut self, layers: &[&str]) -> Self {
+        self.required_validation_layers = layers.iter().map(|layer| layer.to_string()).collect();
+        self
+    }
+
+    pub fn with_message_severity(
+        mut self,
+        message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    ) -> Self {
+        self.message_severity = message_severity;
+        self
+    }
+
+    pub fn with_message_type(mut self, message_type: vk::DebugUtilsMessageTypeFlagsEXT) -> Self {
+        self.message_type = message_type;
+        self
+    }
+
+    pub fn with_capture(mut self, capture: ValidationCapture) -> Self {
+        self.capture = Some(capture);
+        self
+    }
+
+    pub fn with_fail_fast(mut self, is_fail_fast: bool) -> Self {
+        self.is_fail_fast = is_fail_fast;
+        self
+    }
+
+    pub fn with_suppressed_messages(mut self, message_ids: &[&str]) -> Self {
+        self.suppressed_message_ids = message_ids.iter().map(|id| MessageId::parse(id)).collect();
+        self
+    }
+
+    // The layers reject GPU-assisted validation together with debug printf, so printf wins.
+    pub fn with_features(mut self, features: &[ValidationFeature]) -> Self {
+        self.enabled_features = features.to_vec();
+        if features.contains(&ValidationFeature::GpuAssisted)
+            && features.contains(&ValidationFeature::DebugPrintf)
+        {
+            log::warn!(
+                "GPU-assisted validation and debug printf cannot be enabled together, \
+                 disabling GPU-assisted validation."
+            );
+            self.enabled_features
+                .retain(|feature| *feature != ValidationFeature::GpuAssisted);
+        }
+        self
+    }
+
+    pub fn has_feature(&self, feature: ValidationFeature) -> bool {
+        self.is_enable && self.enabled_features.contains(&feature)
+    }
+
+    // Called at the end of a frame, turns captured errors into a RendererError in fail-fast mode.
+    // The capture is drained, so each error is reported once and the buffer doesn't grow.
+    pub fn check_errors(&self) -> RendererResult<()> {
+        match &self.capture {
+            Some(capture) if self.is_fail_fast => {
+                let errors: Vec<String> = capture
+                    .take()
+                    .into_iter()
+                    .filter(|message| {
+                        message
+                            .severity
+                            .contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR)
+                    })
+                    .map(|error| error.to_string())
+                    .collect();
+                if errors.is_empty() {
+                    Ok(())
+                } else {
+                    Err(RendererError::ValidationFailed(errors))
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+pub fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(|item| item.trim()).filter(|item| !item.is_empty())
+}
+
+// unknown names are skipped with a warning rather than failing the whole list
+fn parse_message_severity(value: &str) -> vk::DebugUtilsMessageSeverityFlagsEXT {
+    let mut message_severity = vk::DebugUtilsMessageSeverityFlagsEXT::empty();
+    for name in split_list(value) {
+        message_severity |= match name.to_lowercase().as_str() {
+            "verbose" => vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE,
+            "info" => vk::DebugUtilsMessageSeverityFlagsEXT::INFO,
+            "warning" => vk::DebugUtilsMessageSeverityFlagsEXT::WARNING,
+            "error" => vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+            _ => {
+                log::warn!("Ignoring unknown message severity: {}", name);
+                continue;
+            }
+        };
+    }
+    message_severity
+}
+
+fn parse_message_type(value: &str) -> vk::DebugUtilsMessageTypeFlagsEXT {
+    let mut message_type = vk::DebugUtilsMessageTypeFlagsEXT::empty();
+    for name in split_list(value) {
+        message_type |= match name.to_lowercase().as_str() {
+            "general" => vk::DebugUtilsMessageTypeFlagsEXT::GENERAL,
+            "validation" => vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+            "performance" => vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+            _ => {
+                log::warn!("Ignoring unknown message type: {}", name);
+                continue;
+            }
+        };
+    }
+    message_type
+}
 
 // make a function to convert a raw string array to a string
 fn raw_str_to_str(raw_str: &[c_char]) -> String {
@@ -21,87 +394,168 @@ fn raw_str_to_str(raw_str: &[c_char]) -> String {
     unsafe { CStr::from_ptr(p).to_string_lossy().into_owned() }
 }
 
-pub fn check_validation_layer_support(entry: &ash::Entry) -> bool {
+pub fn check_validation_layer_support(
+    entry: &ash::Entry,
+    validation: &ValidationInfo,
+) -> RendererResult<()> {
     let layer_properties = entry
         .enumerate_instance_layer_properties()
-        .expect("Failed to enumerate Instance Layers Properties!");
-    if layer_properties.len() <= 0 {
-        eprintln!("No available layers.");
-        return false;
+        .context("Failed to enumerate Instance Layers Properties!")?;
+    if layer_properties.is_empty() {
+        log::warn!("No available layers.");
     } else {
-        println!("Instance Available Layers: ");
+        log::info!("Instance Available Layers: ");
         for layer in layer_properties.iter() {
             let layer_name = raw_str_to_str(&layer.layer_name);
-            println!("\t{}", layer_name);
+            log::info!("\t{}", layer_name);
         }
     }
 
-    println!("Checking required Validation Layers: ");
-    let mut is_required_validation_layers_found = true;
-    'outer: for required_validation_layer in VALIDATION.required_validation_layers.iter() {
-        'inner: for layer_property in layer_properties.iter() {
+    log::info!("Checking required Validation Layers: ");
+    let mut missing_validation_layers = vec![];
+    'outer: for required_validation_layer in validation.required_validation_layers.iter() {
+        for layer_property in layer_properties.iter() {
             let layer_name = raw_str_to_str(&layer_property.layer_name);
             if layer_name == *(required_validation_layer) {
-                println!("\t{} is available", required_validation_layer);
+                log::info!("\t{} is available", required_validation_layer);
                 continue 'outer;
             }
         }
-        is_required_validation_layers_found = false;
-        eprintln!("\t{} is not available", required_validation_layer);
+        missing_validation_layers.push(required_validation_layer.to_string());
+        log::warn!("\t{} is not available", required_validation_layer);
+    }
+    if missing_validation_layers.is_empty() {
+        Ok(())
+    } else {
+        Err(RendererError::MissingLayers(missing_validation_layers))
+    }
+}
+
+// p_message_id_name and object names are optional and may be null.
+unsafe fn nullable_str_to_string(p: *const c_char) -> String {
+    if p.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(p).to_string_lossy().into_owned()
     }
-    is_required_validation_layers_found
 }
 
+// Formats the objects attached to a message as `TYPE(0xhandle "name")`.
+unsafe fn describe_objects(callback_data: &vk::DebugUtilsMessengerCallbackDataEXT) -> String {
+    if callback_data.object_count == 0 || callback_data.p_objects.is_null() {
+        return String::new();
+    }
+    std::slice::from_raw_parts(callback_data.p_objects, callback_data.object_count as usize)
+        .iter()
+        .map(|object| {
+            let object_name = nullable_str_to_string(object.p_object_name);
+            if object_name.is_empty() {
+                format!("{:?}(0x{:x})", object.object_type, object.object_handle)
+            } else {
+                format!(
+                    "{:?}(0x{:x} \"{}\")",
+                    object.object_type, object.object_handle, object_name
+                )
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn message_type_name(message_type: vk::DebugUtilsMessageTypeFlagsEXT) -> &'static str {
+    match message_type {
+        vk::DebugUtilsMessageTypeFlagsEXT::GENERAL => "General",
+        vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE => "Performance",
+        vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION => "Validation",
+        _ => "Unknown",
+    }
+}
+
+// Forwards messages to the `log` facade under the "vulkan" target so they are
+// filtered and captured by whatever logger the application installed.
 pub unsafe extern "system" fn debug_utils_callback(
     message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
     message_type: vk::DebugUtilsMessageTypeFlagsEXT,
     p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
-    _p_user_data: *mut c_void,
+    p_user_data: *mut c_void,
 ) -> vk::Bool32 {
-    let severity = match message_severity {
-        vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE => "[Verbose]",
-        vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => "[Warning]",
-        vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => "[Error]",
-        vk::DebugUtilsMessageSeverityFlagsEXT::INFO => "[Info]",
-        _ => "[Unknown]",
-    };
-    let types = match message_type {
-        vk::DebugUtilsMessageTypeFlagsEXT::GENERAL => "[General]",
-        vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE => "[Performance]",
-        vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION => "[Validation]",
-        _ => "[Unknown]",
+    let level = match message_severity {
+        vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE => log::Level::Trace,
+        vk::DebugUtilsMessageSeverityFlagsEXT::INFO => log::Level::Info,
+        vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => log::Level::Warn,
+        vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => log::Level::Error,
+        _ => log::Level::Warn,
     };
-    let message = CStr::from_ptr((*p_callback_data).p_message);
-    println!("[Debug]{}{}{:?}", severity, types, message);
+    let types = message_type_name(message_type);
+    let callback_data = &*p_callback_data;
+    let message = nullable_str_to_string(callback_data.p_message);
+    let message_id_name = nullable_str_to_string(callback_data.p_message_id_name);
+    if !p_user_data.is_null() {
+        let debug_callback_data = &*(p_user_data as *const DebugCallbackData);
+        if debug_callback_data.is_suppressed(&message_id_name, callback_data.message_id_number) {
+            return vk::FALSE;
+        }
+        if let Some(capture) = &debug_callback_data.capture {
+            capture.push(CapturedMessage {
+                severity: message_severity,
+                message_type,
+                message_id_name: message_id_name.clone(),
+                message_id_number: callback_data.message_id_number,
+                message: message.clone(),
+            });
+        }
+    }
+    let objects = describe_objects(callback_data);
+    log::log!(
+        target: "vulkan",
+        level,
+        message_type = types,
+        message_id_name = message_id_name.as_str(),
+        message_id_number = callback_data.message_id_number,
+        objects = objects.as_str();
+        "[{}] {} (0x{:x}) {}",
+        types,
+        message_id_name,
+        callback_data.message_id_number,
+        message
+    );
 
     vk::FALSE
 }
-pub fn populate_debug_messenger_create_info() -> vk::DebugUtilsMessengerCreateInfoEXT {
+pub fn populate_debug_messenger_create_info(
+    validation: &ValidationInfo,
+    debug_callback_data: &DebugCallbackData,
+) -> vk::DebugUtilsMessengerCreateInfoEXT {
+    // debugPrintfEXT output arrives with INFO severity
+    let message_severity = if validation.has_feature(ValidationFeature::DebugPrintf) {
+        validation.message_severity | vk::DebugUtilsMessageSeverityFlagsEXT::INFO
+    } else {
+        validation.message_severity
+    };
     vk::DebugUtilsMessengerCreateInfoEXT {
-        message_severity: vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
-            | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING,
-        message_type: vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
-            | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
-            | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+        message_severity,
+        message_type: validation.message_type,
         pfn_user_callback: Some(debug_utils_callback),
-        p_user_data: ptr::null_mut(),
+        p_user_data: debug_callback_data.as_user_data(),
         ..Default::default()
     }
 }
 pub fn setup_debug_messenger(
     entry: &ash::Entry,
     instance: &ash::Instance,
-) -> Option<(vk::DebugUtilsMessengerEXT)> {
-    if VALIDATION.is_enable {
-        let create_info = populate_debug_messenger_create_info();
+    validation: &ValidationInfo,
+    debug_callback_data: &DebugCallbackData,
+) -> RendererResult<Option<vk::DebugUtilsMessengerEXT>> {
+    if validation.is_enable {
+        let create_info = populate_debug_messenger_create_info(validation, debug_callback_data);
         let debug_utils_messenger = unsafe {
             ext::DebugUtils::new(entry, instance)
                 .create_debug_utils_messenger(&create_info, None)
-                .expect("Debug Utils Callback not found")
+                .context("Debug Utils Callback not found")?
         };
-        Some((debug_utils_messenger))
+        Ok(Some(debug_utils_messenger))
     } else {
-        None
+        Ok(None)
     }
 }
 
@@ -109,12 +563,228 @@ pub fn destroy_debug_messenger(
     entry: &ash::Entry,
     instance: &ash::Instance,
     debug_utils_messenger: Option<vk::DebugUtilsMessengerEXT>,
+    debug_callback_data: &DebugCallbackData,
 ) {
-    match debug_utils_messenger {
-        Some(debug_utils_messenger) => unsafe {
+    if let Some(debug_utils_messenger) = debug_utils_messenger {
+        unsafe {
             ext::DebugUtils::new(entry, instance)
                 .destroy_debug_utils_messenger(debug_utils_messenger, None);
-        },
-        None => {}
+        }
+    }
+    debug_callback_data.log_suppressed_summary();
+}
+
+// Attaches human readable names to handles and command buffer regions, so validation
+// messages and RenderDoc captures don't show raw hex handles.
+// Does nothing when the instance was created without VK_EXT_debug_utils.
+pub struct DebugNamer {
+    debug_utils: Option<ext::DebugUtils>,
+    device: vk::Device,
+}
+
+impl DebugNamer {
+    pub fn new(
+        entry: &ash::Entry,
+        instance: &ash::Instance,
+        device: &ash::Device,
+        validation: &ValidationInfo,
+    ) -> RendererResult<Self> {
+        let is_debug_utils_enable = validation.is_enable
+            || is_instance_extension_available(entry, validation, ext::DebugUtils::name())?;
+        Ok(DebugNamer {
+            debug_utils: if is_debug_utils_enable {
+                Some(ext::DebugUtils::new(entry, instance))
+            } else {
+                None
+            },
+            device: device.handle(),
+        })
+    }
+
+    pub fn set_object_name<T: vk::Handle>(&self, handle: T, name: &str) -> RendererResult<()> {
+        let debug_utils = match &self.debug_utils {
+            Some(debug_utils) => debug_utils,
+            None => return Ok(()),
+        };
+        let name = CString::new(name).unwrap();
+        let name_info = vk::DebugUtilsObjectNameInfoEXT {
+            object_type: T::TYPE,
+            object_handle: handle.as_raw(),
+            p_object_name: name.as_ptr(),
+            ..Default::default()
+        };
+        unsafe {
+            debug_utils
+                .debug_utils_set_object_name(self.device, &name_info)
+                .context("Failed to set debug object name")
+        }
+    }
+
+    // names each handle "<name>[<index>]"
+    pub fn set_object_names<T: vk::Handle + Copy>(
+        &self,
+        handles: &[T],
+        name: &str,
+    ) -> RendererResult<()> {
+        for (i, &handle) in handles.iter().enumerate() {
+            self.set_object_name(handle, &format!("{}[{}]", name, i))?;
+        }
+        Ok(())
+    }
+
+    pub fn begin_label(&self, command_buffer: vk::CommandBuffer, name: &str, color: [f32; 4]) {
+        let debug_utils = match &self.debug_utils {
+            Some(debug_utils) => debug_utils,
+            None => return,
+        };
+        let name = CString::new(name).unwrap();
+        let label = vk::DebugUtilsLabelEXT {
+            p_label_name: name.as_ptr(),
+            color,
+            ..Default::default()
+        };
+        unsafe {
+            debug_utils.cmd_begin_debug_utils_label(command_buffer, &label);
+        }
+    }
+
+    pub fn end_label(&self, command_buffer: vk::CommandBuffer) {
+        if let Some(debug_utils) = &self.debug_utils {
+            unsafe {
+                debug_utils.cmd_end_debug_utils_label(command_buffer);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_message_severity_list() {
+        assert_eq!(
+            parse_message_severity("verbose, ERROR"),
+            vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE
+                | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
+        );
+        assert_eq!(
+            parse_message_severity("info,,bogus"),
+            vk::DebugUtilsMessageSeverityFlagsEXT::INFO
+        );
+        assert!(parse_message_severity("").is_empty());
+    }
+
+    #[test]
+    fn parse_message_type_list() {
+        assert_eq!(
+            parse_message_type("general,performance"),
+            vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE
+        );
+        assert!(parse_message_type("bogus").is_empty());
+    }
+
+    #[test]
+    fn builder_overrides_defaults() {
+        let validation = ValidationInfo::default()
+            .with_enable(true)
+            .with_layers(&["VK_LAYER_LUNARG_api_dump", "VK_LAYER_KHRONOS_validation"])
+            .with_message_severity(vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE)
+            .with_message_type(vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION);
+        assert!(validation.is_enable);
+        assert_eq!(
+            validation.required_validation_layers,
+            vec!["VK_LAYER_LUNARG_api_dump", "VK_LAYER_KHRONOS_validation"]
+        );
+        assert_eq!(
+            validation.message_severity,
+            vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE
+        );
+        assert_eq!(validation.message_type, vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION);
+    }
+
+    fn captured(
+        severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+        message: &str,
+    ) -> CapturedMessage {
+        CapturedMessage {
+            severity,
+            message_type: vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+            message_id_name: "VUID-vkCmdDraw-None-02699".to_string(),
+            message_id_number: 0x1a2b,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn check_errors_reports_each_error_once() {
+        let capture = ValidationCapture::new();
+        let validation = ValidationInfo::default()
+            .with_capture(capture.clone())
+            .with_fail_fast(true);
+        capture.push(captured(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING, "warning"));
+        capture.push(captured(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR, "error"));
+        match validation.check_errors() {
+            Err(RendererError::ValidationFailed(errors)) => assert_eq!(
+                errors,
+                vec!["[Validation] VUID-vkCmdDraw-None-02699 (0x1a2b) error"]
+            ),
+            _ => panic!("expected the captured error"),
+        }
+        assert!(validation.check_errors().is_ok());
+        assert!(capture.take().is_empty());
+    }
+
+    #[test]
+    fn check_errors_needs_fail_fast() {
+        let capture = ValidationCapture::new();
+        let validation = ValidationInfo::default().with_capture(capture.clone());
+        capture.push(captured(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR, "error"));
+        assert!(validation.check_errors().is_ok());
+        assert_eq!(capture.take().len(), 1);
+    }
+
+    #[test]
+    fn parse_message_id() {
+        assert_eq!(MessageId::parse("0x1a2b3c4d"), MessageId::Number(0x1a2b3c4d));
+        assert_eq!(MessageId::parse(" 1234 "), MessageId::Number(1234));
+        assert_eq!(MessageId::parse("-7"), MessageId::Number(-7));
+        // IDs are hashes, the hex form may not fit in an i32 but keeps its bits
+        assert_eq!(MessageId::parse("0xffffffff"), MessageId::Number(-1));
+        assert_eq!(
+            MessageId::parse("VUID-vkCmdDraw-None-02699"),
+            MessageId::Name("VUID-vkCmdDraw-None-02699".to_string())
+        );
+        assert_eq!(MessageId::parse("0xzz"), MessageId::Name("0xzz".to_string()));
+    }
+
+    #[test]
+    fn debug_printf_wins_over_gpu_assisted() {
+        let validation = ValidationInfo::default()
+            .with_enable(true)
+            .with_features(&[
+                ValidationFeature::GpuAssisted,
+                ValidationFeature::BestPractices,
+                ValidationFeature::DebugPrintf,
+            ]);
+        assert!(!validation.has_feature(ValidationFeature::GpuAssisted));
+        assert!(validation.has_feature(ValidationFeature::BestPractices));
+        assert!(validation.has_feature(ValidationFeature::DebugPrintf));
+    }
+
+    #[test]
+    fn suppressed_messages_are_counted_by_id() {
+        let validation = ValidationInfo::default()
+            .with_suppressed_messages(&["VUID-vkCmdDraw-None-02699", "0x10"]);
+        let debug_callback_data = DebugCallbackData::new(&validation);
+        assert!(debug_callback_data.is_suppressed("VUID-vkCmdDraw-None-02699", 1));
+        assert!(debug_callback_data.is_suppressed("VUID-vkCmdDraw-None-02699", 2));
+        assert!(debug_callback_data.is_suppressed("", 0x10));
+        assert!(!debug_callback_data.is_suppressed("VUID-other", 0x11));
+        let suppressed_counts = debug_callback_data.suppressed_counts.lock().unwrap();
+        assert_eq!(suppressed_counts.get("VUID-vkCmdDraw-None-02699"), Some(&2));
+        assert_eq!(suppressed_counts.get("0x10"), Some(&1));
+        assert_eq!(suppressed_counts.len(), 2);
     }
 }
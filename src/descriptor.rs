@@ -0,0 +1,133 @@
+use ash::version::DeviceV1_0;
+use ash::vk;
+
+use crate::error::{RendererResult, VkResultExt};
+use crate::uniform::UniformBufferObject;
+
+// binding 0: the UniformBufferObject read by the vertex shader
+// binding 1: the texture sampled by the fragment shader
+pub fn create_descriptor_set_layout(
+    device: &ash::Device,
+) -> RendererResult<vk::DescriptorSetLayout> {
+    let layout_bindings = [
+        vk::DescriptorSetLayoutBinding {
+            binding: 0,
+            descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::VERTEX,
+            ..Default::default()
+        },
+        vk::DescriptorSetLayoutBinding {
+            binding: 1,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        },
+    ];
+    let layout_create_info = vk::DescriptorSetLayoutCreateInfo {
+        binding_count: layout_bindings.len() as u32,
+        p_bindings: layout_bindings.as_ptr(),
+        ..Default::default()
+    };
+    unsafe {
+        device
+            .create_descriptor_set_layout(&layout_create_info, None)
+            .context("Failed to create Descriptor Set Layout!")
+    }
+}
+
+pub fn create_descriptor_pool(
+    device: &ash::Device,
+    set_count: usize,
+) -> RendererResult<vk::DescriptorPool> {
+    let pool_sizes = [
+        vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::UNIFORM_BUFFER,
+            descriptor_count: set_count as u32,
+        },
+        vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: set_count as u32,
+        },
+    ];
+    let pool_create_info = vk::DescriptorPoolCreateInfo {
+        max_sets: set_count as u32,
+        pool_size_count: pool_sizes.len() as u32,
+        p_pool_sizes: pool_sizes.as_ptr(),
+        ..Default::default()
+    };
+    unsafe {
+        device
+            .create_descriptor_pool(&pool_create_info, None)
+            .context("Failed to create Descriptor Pool!")
+    }
+}
+
+// one set per uniform buffer, in the same order, all sampling the same texture
+pub fn create_descriptor_sets(
+    device: &ash::Device,
+    descriptor_pool: &vk::DescriptorPool,
+    descriptor_set_layout: &vk::DescriptorSetLayout,
+    uniform_buffers: &[vk::Buffer],
+    texture_info: &vk::DescriptorImageInfo,
+) -> RendererResult<Vec<vk::DescriptorSet>> {
+    let layouts = vec![*descriptor_set_layout; uniform_buffers.len()];
+    let descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo {
+        descriptor_pool: *descriptor_pool,
+        descriptor_set_count: layouts.len() as u32,
+        p_set_layouts: layouts.as_ptr(),
+        ..Default::default()
+    };
+    let descriptor_sets = unsafe {
+        device
+            .allocate_descriptor_sets(&descriptor_set_allocate_info)
+            .context("Failed to allocate Descriptor Sets!")?
+    };
+
+    for (&descriptor_set, &uniform_buffer) in descriptor_sets.iter().zip(uniform_buffers.iter()) {
+        let buffer_infos = [vk::DescriptorBufferInfo {
+            buffer: uniform_buffer,
+            offset: 0,
+            range: std::mem::size_of::<UniformBufferObject>() as vk::DeviceSize,
+        }];
+        let descriptor_writes = [vk::WriteDescriptorSet {
+            dst_set: descriptor_set,
+            dst_binding: 0,
+            dst_array_element: 0,
+            descriptor_count: 1,
+            descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+            p_buffer_info: buffer_infos.as_ptr(),
+            ..Default::default()
+        }];
+        unsafe {
+            device.update_descriptor_sets(&descriptor_writes, &[]);
+        }
+    }
+    update_texture_descriptors(device, &descriptor_sets, texture_info);
+
+    Ok(descriptor_sets)
+}
+
+// Points binding 1 of every set at `texture_info`, the sets must not be in use.
+pub fn update_texture_descriptors(
+    device: &ash::Device,
+    descriptor_sets: &[vk::DescriptorSet],
+    texture_info: &vk::DescriptorImageInfo,
+) {
+    let descriptor_writes: Vec<vk::WriteDescriptorSet> = descriptor_sets
+        .iter()
+        .map(|&descriptor_set| vk::WriteDescriptorSet {
+            dst_set: descriptor_set,
+            dst_binding: 1,
+            dst_array_element: 0,
+            descriptor_count: 1,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            p_image_info: texture_info,
+            ..Default::default()
+        })
+        .collect();
+    unsafe {
+        device.update_descriptor_sets(&descriptor_writes, &[]);
+    }
+}
@@ -0,0 +1,257 @@
+use ash::version::InstanceV1_0;
+use ash::vk;
+
+use crate::debug::split_list;
+use crate::error::RendererResult;
+use crate::physical_device::{is_device_extension_supported, missing_device_extensions};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeviceFeature {
+    SamplerAnisotropy,
+    FillModeNonSolid,
+    WideLines,
+    LargePoints,
+    GeometryShader,
+    TessellationShader,
+    DepthClamp,
+    SampleRateShading,
+    MultiDrawIndirect,
+    ShaderInt64,
+}
+
+impl DeviceFeature {
+    // accepts the VkPhysicalDeviceFeatures spelling as well as snake_case
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().replace('_', "").as_str() {
+            "sampleranisotropy" => Some(DeviceFeature::SamplerAnisotropy),
+            "fillmodenonsolid" => Some(DeviceFeature::FillModeNonSolid),
+            "widelines" => Some(DeviceFeature::WideLines),
+            "largepoints" => Some(DeviceFeature::LargePoints),
+            "geometryshader" => Some(DeviceFeature::GeometryShader),
+            "tessellationshader" => Some(DeviceFeature::TessellationShader),
+            "depthclamp" => Some(DeviceFeature::DepthClamp),
+            "samplerateshading" => Some(DeviceFeature::SampleRateShading),
+            "multidrawindirect" => Some(DeviceFeature::MultiDrawIndirect),
+            "shaderint64" => Some(DeviceFeature::ShaderInt64),
+            _ => None,
+        }
+    }
+
+    // same spelling as the VkPhysicalDeviceFeatures member
+    pub fn name(self) -> &'static str {
+        match self {
+            DeviceFeature::SamplerAnisotropy => "samplerAnisotropy",
+            DeviceFeature::FillModeNonSolid => "fillModeNonSolid",
+            DeviceFeature::WideLines => "wideLines",
+            DeviceFeature::LargePoints => "largePoints",
+            DeviceFeature::GeometryShader => "geometryShader",
+            DeviceFeature::TessellationShader => "tessellationShader",
+            DeviceFeature::DepthClamp => "depthClamp",
+            DeviceFeature::SampleRateShading => "sampleRateShading",
+            DeviceFeature::MultiDrawIndirect => "multiDrawIndirect",
+            DeviceFeature::ShaderInt64 => "shaderInt64",
+        }
+    }
+
+    fn field(self, features: &mut vk::PhysicalDeviceFeatures) -> &mut vk::Bool32 {
+        match self {
+            DeviceFeature::SamplerAnisotropy => &mut features.sampler_anisotropy,
+            DeviceFeature::FillModeNonSolid => &mut features.fill_mode_non_solid,
+            DeviceFeature::WideLines => &mut features.wide_lines,
+            DeviceFeature::LargePoints => &mut features.large_points,
+            DeviceFeature::GeometryShader => &mut features.geometry_shader,
+            DeviceFeature::TessellationShader => &mut features.tessellation_shader,
+            DeviceFeature::DepthClamp => &mut features.depth_clamp,
+            DeviceFeature::SampleRateShading => &mut features.sample_rate_shading,
+            DeviceFeature::MultiDrawIndirect => &mut features.multi_draw_indirect,
+            DeviceFeature::ShaderInt64 => &mut features.shader_int64,
+        }
+    }
+
+    pub fn is_supported(self, features: &vk::PhysicalDeviceFeatures) -> bool {
+        let mut features = *features;
+        *self.field(&mut features) == vk::TRUE
+    }
+}
+
+// What the renderer needs from a device on top of swapchain support. Devices missing a
+// required entry are skipped during selection, optional ones are enabled when present.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceRequirements {
+    pub required_features: Vec<DeviceFeature>,
+    pub optional_features: Vec<DeviceFeature>,
+    pub required_extensions: Vec<String>,
+    pub optional_extensions: Vec<String>,
+}
+
+impl DeviceRequirements {
+    // Starts with no requirements and applies, when set:
+    //   VULKAN_DEVICE_FEATURES=samplerAnisotropy,wideLines,...
+    //   VULKAN_DEVICE_OPTIONAL_FEATURES=fill_mode_non_solid,...
+    //   VULKAN_DEVICE_EXTENSIONS=VK_KHR_...,...
+    //   VULKAN_DEVICE_OPTIONAL_EXTENSIONS=VK_EXT_...,...
+    pub fn from_env() -> Self {
+        let mut requirements = DeviceRequirements::default();
+        if let Ok(value) = std::env::var("VULKAN_DEVICE_FEATURES") {
+            requirements = requirements.with_required_features(&parse_features(&value));
+        }
+        if let Ok(value) = std::env::var("VULKAN_DEVICE_OPTIONAL_FEATURES") {
+            requirements = requirements.with_optional_features(&parse_features(&value));
+        }
+        if let Ok(value) = std::env::var("VULKAN_DEVICE_EXTENSIONS") {
+            let extensions: Vec<&str> = split_list(&value).collect();
+            requirements = requirements.with_required_extensions(&extensions);
+        }
+        if let Ok(value) = std::env::var("VULKAN_DEVICE_OPTIONAL_EXTENSIONS") {
+            let extensions: Vec<&str> = split_list(&value).collect();
+            requirements = requirements.with_optional_extensions(&extensions);
+        }
+        requirements
+    }
+
+    pub fn with_required_features(mut self, features: &[DeviceFeature]) -> Self {
+        self.required_features = features.to_vec();
+        self
+    }
+
+    pub fn with_optional_features(mut self, features: &[DeviceFeature]) -> Self {
+        self.optional_features = features.to_vec();
+        self
+    }
+
+    pub fn with_required_extensions(mut self, extensions: &[&str]) -> Self {
+        self.required_extensions = extensions.iter().map(|name| name.to_string()).collect();
+        self
+    }
+
+    pub fn with_optional_extensions(mut self, extensions: &[&str]) -> Self {
+        self.optional_extensions = extensions.iter().map(|name| name.to_string()).collect();
+        self
+    }
+
+    // names of the required features and extensions the device lacks
+    pub fn missing_requirements(
+        &self,
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+    ) -> RendererResult<Vec<String>> {
+        let supported_features = unsafe { instance.get_physical_device_features(physical_device) };
+        let mut missing: Vec<String> = self
+            .required_features
+            .iter()
+            .filter(|feature| !feature.is_supported(&supported_features))
+            .map(|feature| feature.name().to_string())
+            .collect();
+        let required_extensions: Vec<&str> =
+            self.required_extensions.iter().map(|name| name.as_str()).collect();
+        missing.extend(missing_device_extensions(
+            instance,
+            physical_device,
+            &required_extensions,
+        )?);
+        Ok(missing)
+    }
+
+    // Assumes the device passed missing_requirements.
+    pub fn negotiate(
+        &self,
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+    ) -> RendererResult<EnabledDeviceFeatures> {
+        let supported_features = unsafe { instance.get_physical_device_features(physical_device) };
+        let mut enabled = EnabledDeviceFeatures {
+            features: self.required_features.clone(),
+            extensions: self.required_extensions.clone(),
+        };
+        for &feature in self.optional_features.iter() {
+            if feature.is_supported(&supported_features) && !enabled.is_feature_enabled(feature) {
+                enabled.features.push(feature);
+            }
+        }
+        for extension in self.optional_extensions.iter() {
+            if is_device_extension_supported(instance, physical_device, extension)?
+                && !enabled.is_extension_enabled(extension)
+            {
+                enabled.extensions.push(extension.clone());
+            }
+        }
+        Ok(enabled)
+    }
+}
+
+// unknown names are skipped with a warning rather than failing the whole list
+fn parse_features(value: &str) -> Vec<DeviceFeature> {
+    let mut features = vec![];
+    for name in split_list(value) {
+        match DeviceFeature::parse(name) {
+            Some(feature) => features.push(feature),
+            None => log::warn!("Ignoring unknown device feature: {}", name),
+        }
+    }
+    features
+}
+
+// The required entries plus whichever optional ones the picked device supports.
+#[derive(Debug, Clone, Default)]
+pub struct EnabledDeviceFeatures {
+    pub features: Vec<DeviceFeature>,
+    pub extensions: Vec<String>,
+}
+
+impl EnabledDeviceFeatures {
+    pub fn is_feature_enabled(&self, feature: DeviceFeature) -> bool {
+        self.features.contains(&feature)
+    }
+
+    pub fn is_extension_enabled(&self, extension_name: &str) -> bool {
+        self.extensions.iter().any(|extension| extension == extension_name)
+    }
+
+    pub fn physical_device_features(&self) -> vk::PhysicalDeviceFeatures {
+        let mut physical_device_features = vk::PhysicalDeviceFeatures {
+            ..Default::default()
+        };
+        for feature in self.features.iter() {
+            *feature.field(&mut physical_device_features) = vk::TRUE;
+        }
+        physical_device_features
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_feature_names() {
+        assert_eq!(
+            DeviceFeature::parse("samplerAnisotropy"),
+            Some(DeviceFeature::SamplerAnisotropy)
+        );
+        assert_eq!(
+            DeviceFeature::parse(" fill_mode_non_solid "),
+            Some(DeviceFeature::FillModeNonSolid)
+        );
+        assert_eq!(DeviceFeature::parse("bogus"), None);
+        assert_eq!(
+            parse_features("wideLines,,bogus,shaderInt64"),
+            vec![DeviceFeature::WideLines, DeviceFeature::ShaderInt64]
+        );
+    }
+
+    #[test]
+    fn enabled_features_set_only_their_fields() {
+        let enabled = EnabledDeviceFeatures {
+            features: vec![DeviceFeature::SamplerAnisotropy, DeviceFeature::WideLines],
+            extensions: vec!["VK_KHR_shader_non_semantic_info".to_string()],
+        };
+        let physical_device_features = enabled.physical_device_features();
+        assert_eq!(physical_device_features.sampler_anisotropy, vk::TRUE);
+        assert_eq!(physical_device_features.wide_lines, vk::TRUE);
+        assert_eq!(physical_device_features.fill_mode_non_solid, vk::FALSE);
+        assert!(DeviceFeature::WideLines.is_supported(&physical_device_features));
+        assert!(!DeviceFeature::LargePoints.is_supported(&physical_device_features));
+        assert!(enabled.is_extension_enabled("VK_KHR_shader_non_semantic_info"));
+        assert!(!enabled.is_feature_enabled(DeviceFeature::DepthClamp));
+    }
+}
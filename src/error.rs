@@ -0,0 +1,160 @@
+use ash::vk;
+use std::fmt;
+
+pub type RendererResult<T> = Result<T, RendererError>;
+
+#[derive(Debug)]
+pub enum RendererError {
+    MissingLayers(Vec<String>),
+    MissingExtensions(Vec<String>),
+    NoSuitableGpu,
+    NoSuitableQueueFamily,
+    NoSuitableMemoryType,
+    MemoryNotMapped,
+    EmptyBuffer,
+    IndexOutOfRange { index: u32, vertex_count: usize },
+    PushConstantsTooLarge { size: u32, max: u32 },
+    ShaderIo { path: String, source: std::io::Error },
+    ImageLoad { path: String, source: image::ImageError },
+    ModelLoad { path: String, source: tobj::LoadError },
+    SurfaceLost,
+    OutOfDateSwapchain,
+    DeviceLost,
+    ValidationFailed(Vec<String>),
+    Loading(ash::LoadingError),
+    Window(winit::error::OsError),
+    Report(serde_json::Error),
+    Vulkan { context: &'static str, result: vk::Result },
+}
+
+impl RendererError {
+    // Sort the vk::Result codes callers are expected to react to into their own variants.
+    pub fn from_vk(context: &'static str, result: vk::Result) -> Self {
+        match result {
+            vk::Result::ERROR_SURFACE_LOST_KHR => RendererError::SurfaceLost,
+            vk::Result::ERROR_OUT_OF_DATE_KHR => RendererError::OutOfDateSwapchain,
+            vk::Result::ERROR_DEVICE_LOST => RendererError::DeviceLost,
+            _ => RendererError::Vulkan { context, result },
+        }
+    }
+}
+
+impl fmt::Display for RendererError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RendererError::MissingLayers(layers) => write!(
+                f,
+                "Validation layers requested, but not available: {}",
+                layers.join(", ")
+            ),
+            RendererError::MissingExtensions(extensions) => write!(
+                f,
+                "Extensions requested, but not available: {}",
+                extensions.join(", ")
+            ),
+            RendererError::NoSuitableGpu => write!(f, "Failed to find a suitable GPU!"),
+            RendererError::NoSuitableQueueFamily => {
+                write!(f, "Failed to find a suitable queue family")
+            }
+            RendererError::NoSuitableMemoryType => {
+                write!(f, "Failed to find suitable memory type!")
+            }
+            RendererError::MemoryNotMapped => {
+                write!(f, "Host visible memory is not mapped")
+            }
+            RendererError::EmptyBuffer => write!(f, "Cannot create a buffer from empty data"),
+            RendererError::IndexOutOfRange {
+                index,
+                vertex_count,
+            } => write!(
+                f,
+                "Index {} is out of range for {} vertices",
+                index, vertex_count
+            ),
+            RendererError::PushConstantsTooLarge { size, max } => write!(
+                f,
+                "Push constants need {} bytes, but maxPushConstantsSize is {}",
+                size, max
+            ),
+            RendererError::ShaderIo { path, source } => {
+                write!(f, "Failed to read shader file {}: {}", path, source)
+            }
+            RendererError::ImageLoad { path, source } => {
+                write!(f, "Failed to load image {}: {}", path, source)
+            }
+            RendererError::ModelLoad { path, source } => {
+                write!(f, "Failed to load model {}: {}", path, source)
+            }
+            RendererError::SurfaceLost => write!(f, "The window surface was lost"),
+            RendererError::OutOfDateSwapchain => write!(f, "The swapchain is out of date"),
+            RendererError::DeviceLost => write!(f, "The logical device was lost"),
+            RendererError::ValidationFailed(messages) => write!(
+                f,
+                "{} validation error(s) were reported:\n{}",
+                messages.len(),
+                messages.join("\n")
+            ),
+            RendererError::Loading(err) => write!(f, "Failed to load Vulkan: {}", err),
+            RendererError::Window(err) => write!(f, "Failed to create window: {}", err),
+            RendererError::Report(err) => write!(f, "Failed to serialize the report: {}", err),
+            RendererError::Vulkan { context, result } => write!(f, "{}: {}", context, result),
+        }
+    }
+}
+
+impl std::error::Error for RendererError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RendererError::ShaderIo { source, .. } => Some(source),
+            RendererError::ImageLoad { source, .. } => Some(source),
+            RendererError::ModelLoad { source, .. } => Some(source),
+            RendererError::Loading(err) => Some(err),
+            RendererError::Window(err) => Some(err),
+            RendererError::Report(err) => Some(err),
+            RendererError::Vulkan { result, .. } => Some(result),
+            _ => None,
+        }
+    }
+}
+
+impl From<ash::LoadingError> for RendererError {
+    fn from(err: ash::LoadingError) -> Self {
+        RendererError::Loading(err)
+    }
+}
+
+impl From<winit::error::OsError> for RendererError {
+    fn from(err: winit::error::OsError) -> Self {
+        RendererError::Window(err)
+    }
+}
+
+impl From<serde_json::Error> for RendererError {
+    fn from(err: serde_json::Error) -> Self {
+        RendererError::Report(err)
+    }
+}
+
+impl From<ash::InstanceError> for RendererError {
+    fn from(err: ash::InstanceError) -> Self {
+        match err {
+            ash::InstanceError::VkError(result) => {
+                RendererError::from_vk("Failed to create instance", result)
+            }
+            ash::InstanceError::LoadError(symbols) => RendererError::Loading(
+                ash::LoadingError::LibraryLoadError(symbols.join(", ")),
+            ),
+        }
+    }
+}
+
+// Lets call sites keep the `.expect("...")` shape: `.context("...")?`.
+pub trait VkResultExt<T> {
+    fn context(self, context: &'static str) -> RendererResult<T>;
+}
+
+impl<T> VkResultExt<T> for Result<T, vk::Result> {
+    fn context(self, context: &'static str) -> RendererResult<T> {
+        self.map_err(|result| RendererError::from_vk(context, result))
+    }
+}
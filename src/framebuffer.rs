@@ -1,7 +1,9 @@
 use ash::vk;
 use ash::version::DeviceV1_0;
 use std::ptr;
-pub fn create_framebuffer(device:&ash::Device,image_views:&Vec<vk::ImageView>,render_pass:&vk::RenderPass,swapchain_extent:&vk::Extent2D)->Vec<vk::Framebuffer> {
+
+use crate::error::{RendererResult, VkResultExt};
+pub fn create_framebuffer(device:&ash::Device,image_views:&[vk::ImageView],render_pass:&vk::RenderPass,swapchain_extent:&vk::Extent2D)->RendererResult<Vec<vk::Framebuffer>> {
 //    image_views.iter().map(|image_view| {
 //         let attachments = [*image_view];
 //         let framebuffer_info = 
@@ -34,11 +36,20 @@ for &image_view in image_views.iter() {
     let framebuffer = unsafe {
         device
             .create_framebuffer(&framebuffer_create_info, None)
-            .expect("Failed to create Framebuffer!")
+            .context("Failed to create Framebuffer!")
+    };
+    let framebuffer = match framebuffer {
+        Ok(framebuffer) => framebuffer,
+        Err(err) => {
+            for framebuffer in framebuffers {
+                unsafe { device.destroy_framebuffer(framebuffer, None) };
+            }
+            return Err(err);
+        }
     };
 
     framebuffers.push(framebuffer);
 }
 
-framebuffers
+Ok(framebuffers)
 }
\ No newline at end of file
@@ -0,0 +1,389 @@
+use ash::version::DeviceV1_0;
+use ash::vk;
+use cgmath::{Matrix4, SquareMatrix};
+use std::ptr;
+
+use crate::allocator::{Allocation, Allocator};
+use crate::cleanup::CleanupGuard;
+use crate::command::{create_command_buffers, DrawStuff};
+use crate::config::CoreConfig;
+use crate::context::Context;
+use crate::device_features::EnabledDeviceFeatures;
+use crate::error::{RendererError, RendererResult, VkResultExt};
+use crate::framebuffer::create_framebuffer;
+use crate::pipeline::{create_graphic_pipeline, create_render_pass};
+use crate::push_constant::PushConstants;
+use crate::queue::QueueFamilyIndices;
+use crate::swapchain::create_image_view;
+use crate::uniform::{
+    create_uniform_stuff, default_view, destroy_uniform_stuff, update_uniform_buffer,
+    UniformBufferObject, UniformStuff,
+};
+
+const OFFSCREEN_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+
+pub struct OffscreenStuff {
+    pub image: vk::Image,
+    pub allocation: Allocation,
+    pub image_view: vk::ImageView,
+    pub format: vk::Format,
+    pub extent: vk::Extent2D,
+}
+
+// Renders the same render pass and pipeline as Core into an engine owned image,
+// so it runs on machines without a display (e.g. lavapipe on CI).
+pub struct HeadlessCore {
+    context: Context,
+    offscreen: OffscreenStuff,
+    pipeline: vk::Pipeline,
+    pipeline_layout: vk::PipelineLayout,
+    render_pass: vk::RenderPass,
+    frame_buffers: Vec<vk::Framebuffer>,
+    command_buffer: Vec<vk::CommandBuffer>,
+    uniform_stuff: UniformStuff,
+    render_finished_fence: vk::Fence,
+}
+
+pub fn create_offscreen_image(
+    device: &ash::Device,
+    allocator: &mut Allocator,
+    format: vk::Format,
+    extent: vk::Extent2D,
+) -> RendererResult<OffscreenStuff> {
+    let image_create_info = vk::ImageCreateInfo {
+        image_type: vk::ImageType::TYPE_2D,
+        format,
+        extent: vk::Extent3D {
+            width: extent.width,
+            height: extent.height,
+            depth: 1,
+        },
+        mip_levels: 1,
+        array_layers: 1,
+        samples: vk::SampleCountFlags::TYPE_1,
+        tiling: vk::ImageTiling::OPTIMAL,
+        usage: vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+        sharing_mode: vk::SharingMode::EXCLUSIVE,
+        initial_layout: vk::ImageLayout::UNDEFINED,
+        ..Default::default()
+    };
+    let (image, allocation) =
+        allocator.create_image(&image_create_info, vk::MemoryPropertyFlags::DEVICE_LOCAL)?;
+
+    let image_view =
+        match create_image_view(device, image, format, vk::ImageAspectFlags::COLOR, 1) {
+            Ok(image_view) => image_view,
+            Err(err) => {
+                allocator.destroy_image(image, &allocation);
+                return Err(err);
+            }
+        };
+
+    Ok(OffscreenStuff {
+        image,
+        allocation,
+        image_view,
+        format,
+        extent,
+    })
+}
+
+impl HeadlessCore {
+    pub fn build(
+        width: u32,
+        height: u32,
+        config: &CoreConfig,
+    ) -> RendererResult<Self> {
+        let mut context = Context::build(None, config)?;
+        // declared after the context, so on an early return it runs before the
+        // context destroys the device
+        let mut guard = CleanupGuard::new();
+        let device = &context.device;
+
+        let extent = vk::Extent2D { width, height };
+        // the context's allocator frees its memory if a later step fails
+        let offscreen =
+            create_offscreen_image(device, &mut context.allocator, OFFSCREEN_FORMAT, extent)?;
+        let (offscreen_image, offscreen_image_view) = (offscreen.image, offscreen.image_view);
+        guard.push_with(device, move |device| unsafe {
+            device.destroy_image_view(offscreen_image_view, None);
+            device.destroy_image(offscreen_image, None);
+        });
+        let render_pass = create_render_pass(
+            device,
+            offscreen.format,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        )?;
+        guard.push_with(device, move |device| unsafe {
+            device.destroy_render_pass(render_pass, None)
+        });
+        let (pipeline, pipeline_layout) =
+            create_graphic_pipeline(
+            device,
+            &render_pass,
+            &context.descriptor_set_layout,
+            &[context.push_constant_range],
+        )?;
+        guard.push_with(device, move |device| unsafe {
+            device.destroy_pipeline(pipeline, None);
+            device.destroy_pipeline_layout(pipeline_layout, None);
+        });
+        let frame_buffers = create_framebuffer(
+            device,
+            &[offscreen.image_view],
+            &render_pass,
+            &offscreen.extent,
+        )?;
+        let framebuffers = frame_buffers.clone();
+        guard.push_with(device, move |device| {
+            for framebuffer in framebuffers {
+                unsafe { device.destroy_framebuffer(framebuffer, None) };
+            }
+        });
+        let texture_info = context.texture_image_info();
+        let uniform_stuff = create_uniform_stuff(
+            device,
+            &mut context.allocator,
+            &context.descriptor_set_layout,
+            1,
+            &texture_info,
+        )?;
+        // the context's allocator frees its memory
+        let (descriptor_pool, uniform_buffer) =
+            (uniform_stuff.descriptor_pool, uniform_stuff.uniform_buffers[0].0);
+        guard.push_with(device, move |device| unsafe {
+            device.destroy_descriptor_pool(descriptor_pool, None);
+            device.destroy_buffer(uniform_buffer, None);
+        });
+        // a single still frame, so the model matrix is written once here
+        update_uniform_buffer(
+            &uniform_stuff.uniform_buffers[0].1,
+            &UniformBufferObject::new(Matrix4::identity(), default_view(), offscreen.extent),
+        );
+        // freed along with the command pool if a later step fails
+        let command_buffer = create_command_buffers(
+            device,
+            &context.command_pool,
+            &frame_buffers,
+            &render_pass,
+            &offscreen.extent,
+            &DrawStuff {
+                graphics_pipeline: pipeline,
+                pipeline_layout,
+                descriptor_sets: &uniform_stuff.descriptor_sets,
+                push_constant_range: context.push_constant_range,
+                push_constants: PushConstants::default(),
+                vertex_buffer: context.geometry.vertex_buffer,
+                index_buffer: context.geometry.index_buffer.buffer,
+                index_type: context.geometry.index_buffer.index_type,
+                index_count: context.geometry.index_buffer.index_count,
+            },
+            &context.debug_namer,
+        )?;
+
+        let fence_create_info = vk::FenceCreateInfo {
+            s_type: vk::StructureType::FENCE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::FenceCreateFlags::empty(),
+        };
+        let render_finished_fence = unsafe {
+            device
+                .create_fence(&fence_create_info, None)
+                .context("Failed to create Fence Object!")?
+        };
+        guard.push_with(device, move |device| unsafe {
+            device.destroy_fence(render_finished_fence, None)
+        });
+
+        let debug_namer = &context.debug_namer;
+        debug_namer.set_object_name(offscreen.image, "Offscreen Image")?;
+        debug_namer.set_object_name(offscreen.image_view, "Offscreen Image View")?;
+        debug_namer.set_object_name(render_pass, "Offscreen Render Pass")?;
+        debug_namer.set_object_name(pipeline, "Graphics Pipeline")?;
+        debug_namer.set_object_name(pipeline_layout, "Graphics Pipeline Layout")?;
+        debug_namer.set_object_names(&frame_buffers, "Framebuffer")?;
+        debug_namer.set_object_name(uniform_buffer, "Uniform Buffer")?;
+        debug_namer.set_object_name(descriptor_pool, "UBO Descriptor Pool")?;
+        debug_namer.set_object_names(&uniform_stuff.descriptor_sets, "UBO Descriptor Set")?;
+        debug_namer.set_object_name(render_finished_fence, "Render Finished Fence")?;
+
+        guard.disarm();
+        Ok(HeadlessCore {
+            context,
+            offscreen,
+            pipeline,
+            pipeline_layout,
+            render_pass,
+            frame_buffers,
+            command_buffer,
+            uniform_stuff,
+            render_finished_fence,
+        })
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.offscreen.extent
+    }
+
+    // the instance API version negotiated with the loader
+    pub fn api_version(&self) -> u32 {
+        self.context.api_version
+    }
+
+    // the transfer and compute families are only set when the device has dedicated ones
+    pub fn queue_family_indices(&self) -> &QueueFamilyIndices {
+        &self.context.queue_family
+    }
+
+    // the required device features and extensions plus the optional ones that were available
+    pub fn enabled_device_features(&self) -> &EnabledDeviceFeatures {
+        &self.context.enabled_device_features
+    }
+
+    // Submits the recorded frame and blocks until the GPU has finished it.
+    pub fn burn_frame(&mut self) -> RendererResult<()> {
+        let device = &self.context.device;
+        let submit_infos = [vk::SubmitInfo {
+            command_buffer_count: 1,
+            p_command_buffers: &self.command_buffer[0],
+            ..Default::default()
+        }];
+        unsafe {
+            device
+                .queue_submit(
+                    self.context.graphics_queue,
+                    &submit_infos,
+                    self.render_finished_fence,
+                )
+                .context("Failed to execute queue submit.")?;
+            device
+                .wait_for_fences(&[self.render_finished_fence], true, u64::MAX)
+                .context("Failed to wait for Fence!")?;
+            device
+                .reset_fences(&[self.render_finished_fence])
+                .context("Failed to reset Fence!")?;
+        }
+        self.context.validation.check_errors()
+    }
+
+    // Copies the last rendered frame back to the host as tightly packed RGBA8 rows.
+    pub fn read_pixels(&mut self) -> RendererResult<Vec<u8>> {
+        let extent = self.offscreen.extent;
+        let buffer_size = (extent.width * extent.height * 4) as vk::DeviceSize;
+
+        let (readback_buffer, readback_allocation) = self.context.allocator.create_buffer(
+            buffer_size,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+        let result = self
+            .copy_offscreen_to_buffer(readback_buffer)
+            .and_then(|_| match readback_allocation.mapped_ptr() {
+                Some(data_ptr) => Ok(unsafe {
+                    std::slice::from_raw_parts(data_ptr as *const u8, buffer_size as usize)
+                        .to_vec()
+                }),
+                None => Err(RendererError::MemoryNotMapped),
+            });
+        self.context
+            .allocator
+            .destroy_buffer(readback_buffer, &readback_allocation);
+        result
+    }
+
+    // The render pass leaves the offscreen image in TRANSFER_SRC_OPTIMAL, and its
+    // 0 -> EXTERNAL dependency makes the color writes visible to this copy.
+    fn copy_offscreen_to_buffer(&self, buffer: vk::Buffer) -> RendererResult<()> {
+        let device = &self.context.device;
+        let extent = self.offscreen.extent;
+        let command_buffer_allocate_info = vk::CommandBufferAllocateInfo {
+            command_buffer_count: 1,
+            command_pool: self.context.command_pool,
+            level: vk::CommandBufferLevel::PRIMARY,
+            ..Default::default()
+        };
+        let copy_command_buffer = unsafe {
+            device
+                .allocate_command_buffers(&command_buffer_allocate_info)
+                .context("Failed to allocate Command Buffers!")?[0]
+        };
+        let command_buffer_begin_info = vk::CommandBufferBeginInfo {
+            flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            ..Default::default()
+        };
+        let copy_region = vk::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+            image_extent: vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            },
+        };
+        let result = unsafe {
+            device
+                .begin_command_buffer(copy_command_buffer, &command_buffer_begin_info)
+                .context("Failed to begin recording Command Buffer at beginning!")
+                .and_then(|_| {
+                    device.cmd_copy_image_to_buffer(
+                        copy_command_buffer,
+                        self.offscreen.image,
+                        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                        buffer,
+                        &[copy_region],
+                    );
+                    device
+                        .end_command_buffer(copy_command_buffer)
+                        .context("Failed to record Command Buffer at Ending!")
+                })
+                .and_then(|_| {
+                    let submit_infos = [vk::SubmitInfo {
+                        command_buffer_count: 1,
+                        p_command_buffers: &copy_command_buffer,
+                        ..Default::default()
+                    }];
+                    device
+                        .queue_submit(self.context.graphics_queue, &submit_infos, vk::Fence::null())
+                        .context("Failed to execute queue submit.")?;
+                    device
+                        .queue_wait_idle(self.context.graphics_queue)
+                        .context("Failed to wait queue idle.")
+                })
+        };
+        unsafe {
+            device.free_command_buffers(self.context.command_pool, &[copy_command_buffer]);
+        }
+        result
+    }
+}
+
+impl Drop for HeadlessCore {
+    fn drop(&mut self) {
+        let device = &self.context.device;
+        unsafe {
+            // nothing can be destroyed while the GPU still uses it, and there is no
+            // one left to report a failure to
+            let _ = device.device_wait_idle();
+            device.destroy_fence(self.render_finished_fence, None);
+            for framebuffer in self.frame_buffers.iter() {
+                device.destroy_framebuffer(*framebuffer, None);
+            }
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_render_pass(self.render_pass, None);
+            destroy_uniform_stuff(device, &mut self.context.allocator, &mut self.uniform_stuff);
+            device.destroy_image_view(self.offscreen.image_view, None);
+            self.context
+                .allocator
+                .destroy_image(self.offscreen.image, &self.offscreen.allocation);
+        }
+    }
+}
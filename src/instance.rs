@@ -1,71 +1,293 @@
-use ash::extensions::{ext, khr};
-use ash::version::{DeviceV1_0, EntryV1_0, InstanceV1_0};
+use ash::extensions::ext;
+use ash::version::EntryV1_0;
 use ash::vk;
-use ash::vk_make_version;
+use ash::{vk_make_version, vk_version_major, vk_version_minor, vk_version_patch};
 use ash::Entry;
 
-use std::ffi::{CString, c_void};
+use std::ffi::{CStr, CString, c_void};
 use std::ptr;
 use crate::debug::{
-    check_validation_layer_support, populate_debug_messenger_create_info, VALIDATION,
+    check_validation_layer_support, populate_debug_messenger_create_info, DebugCallbackData,
+    ValidationInfo,
 };
+use crate::error::{RendererError, RendererResult, VkResultExt};
+use crate::surface::required_extension_names;
 
-pub fn create_instance(entry: &Entry) -> ash::Instance {
-    if VALIDATION.is_enable && check_validation_layer_support(entry) == false {
-        panic!("Validation layers requested, but not available!");
+#[derive(Debug, Clone)]
+pub struct AppInfo {
+    // falls back to the window title, or "Hello" when headless
+    pub application_name: Option<String>,
+    pub application_version: u32,
+    pub engine_name: String,
+    pub engine_version: u32,
+    pub max_api_version: u32,
+}
+
+impl Default for AppInfo {
+    fn default() -> Self {
+        AppInfo {
+            application_name: None,
+            application_version: vk_make_version!(1, 0, 0),
+            engine_name: "Engine".to_string(),
+            engine_version: vk_make_version!(1, 0, 0),
+            max_api_version: vk_make_version!(1, 1, 0),
+        }
+    }
+}
+
+// Loaders older than 1.1 don't export vkEnumerateInstanceVersion and only support 1.0.
+pub fn negotiate_api_version(entry: &Entry, max_api_version: u32) -> RendererResult<u32> {
+    let loader_version = entry
+        .try_enumerate_instance_version()
+        .context("Failed to enumerate instance version")?
+        .unwrap_or(vk_make_version!(1, 0, 0));
+    // the patch number is not part of the API version a loader has to support
+    let loader_version = vk_make_version!(
+        vk_version_major!(loader_version),
+        vk_version_minor!(loader_version),
+        0
+    );
+    Ok(loader_version.min(max_api_version))
+}
+
+// Takes "major.minor" as written in the spec, a patch number is accepted and dropped.
+pub fn parse_api_version(value: &str) -> Option<u32> {
+    let numbers: Vec<u32> = value
+        .trim()
+        .split('.')
+        .map(|number| number.parse().ok())
+        .collect::<Option<_>>()?;
+    match numbers[..] {
+        [major, minor] | [major, minor, _] => Some(vk_make_version!(major, minor, 0)),
+        _ => None,
+    }
+}
+
+pub fn format_version(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        vk_version_major!(version),
+        vk_version_minor!(version),
+        vk_version_patch!(version)
+    )
+}
+
+pub fn create_instance(
+    entry: &Entry,
+    window: Option<&winit::window::Window>,
+    app_info: &AppInfo,
+    api_version: u32,
+    validation: &ValidationInfo,
+    debug_callback_data: &DebugCallbackData,
+) -> RendererResult<ash::Instance> {
+    if validation.is_enable {
+        check_validation_layer_support(entry, validation)?;
     }
 
-    let app_name = CString::new("Hello").unwrap();
-    let engine_name = CString::new("Engine").unwrap();
-    let app_info = vk::ApplicationInfo {
-        application_version: vk_make_version!(1, 0, 0),
+    let app_name =
+        CString::new(app_info.application_name.as_ref().map_or("Hello", |name| name.as_str()))
+            .unwrap();
+    let engine_name = CString::new(app_info.engine_name.as_str()).unwrap();
+    let application_info = vk::ApplicationInfo {
+        application_version: app_info.application_version,
         p_application_name: app_name.as_ptr(),
-        engine_version: vk_make_version!(1, 0, 0),
+        engine_version: app_info.engine_version,
         p_engine_name: engine_name.as_ptr(),
-        api_version: vk_make_version!(1, 0, 0),
+        api_version,
         ..Default::default()
     };
 
-    let required_extensions: Vec<*const i8> = vec![
-        khr::Surface::name().as_ptr(),
-        khr::Win32Surface::name().as_ptr(),
-        ext::DebugUtils::name().as_ptr(),
-    ];
+    // headless instances render offscreen and never create a surface
+    let mut required_extensions = match window {
+        Some(window) => required_extension_names(entry, window)?,
+        None => vec![],
+    };
+    // the messenger needs debug utils, without validation it is only used for object names
+    let is_debug_utils_available =
+        is_instance_extension_available(entry, validation, ext::DebugUtils::name())?;
+    if validation.is_enable || is_debug_utils_available {
+        required_extensions.push(ext::DebugUtils::name().as_ptr());
+    }
+
+    let enabled_validation_features: Vec<vk::ValidationFeatureEnableEXT> = validation
+        .enabled_features
+        .iter()
+        .map(|feature| feature.to_vk())
+        .collect();
+    let mut is_validation_features_enable =
+        validation.is_enable && !enabled_validation_features.is_empty();
+    if is_validation_features_enable
+        && !is_instance_extension_available(entry, validation, vk::ExtValidationFeaturesFn::name())?
+    {
+        log::warn!("VK_EXT_validation_features is not available, validation features are disabled");
+        is_validation_features_enable = false;
+    }
+    if is_validation_features_enable {
+        required_extensions.push(vk::ExtValidationFeaturesFn::name().as_ptr());
+    }
+    let validation_features = vk::ValidationFeaturesEXT {
+        enabled_validation_feature_count: enabled_validation_features.len() as u32,
+        p_enabled_validation_features: enabled_validation_features.as_ptr(),
+        ..Default::default()
+    };
 
-    let requred_validation_layer_raw_names: Vec<CString> = VALIDATION
+    let requred_validation_layer_raw_names: Vec<CString> = validation
         .required_validation_layers
         .iter()
-        .map(|layer_name| CString::new(*layer_name).unwrap())
+        .map(|layer_name| CString::new(layer_name.as_str()).unwrap())
         .collect();
+    let mut debug_messenger_create_info =
+        populate_debug_messenger_create_info(validation, debug_callback_data);
+    if is_validation_features_enable {
+        debug_messenger_create_info.p_next =
+            &validation_features as *const vk::ValidationFeaturesEXT as *const c_void;
+    }
     let enable_layer_names: Vec<*const i8> = requred_validation_layer_raw_names
         .iter()
         .map(|layer_name| layer_name.as_ptr())
         .collect();
 
+    check_instance_extension_support(entry, validation, &required_extensions)?;
+
     let create_info = vk::InstanceCreateInfo {
-        p_application_info: &app_info,
+        p_application_info: &application_info,
 
         enabled_extension_count: required_extensions.len() as u32,
         pp_enabled_extension_names: required_extensions.as_ptr(),
 
-        pp_enabled_layer_names: if VALIDATION.is_enable {
+        pp_enabled_layer_names: if validation.is_enable {
             enable_layer_names.as_ptr()
         } else {
             ptr::null()
         },
-        enabled_layer_count: if VALIDATION.is_enable {
+        enabled_layer_count: if validation.is_enable {
             enable_layer_names.len()
         } else {
             0
         } as u32,
-        p_next: if VALIDATION.is_enable {
-            &populate_debug_messenger_create_info() as *const vk::DebugUtilsMessengerCreateInfoEXT
+        p_next: if validation.is_enable {
+            &debug_messenger_create_info as *const vk::DebugUtilsMessengerCreateInfoEXT
                 as *const c_void
         } else {
             ptr::null()
         },
         ..Default::default()
     };
-    let instance = unsafe { entry.create_instance(&create_info, None).unwrap() };
-    instance
+    unsafe { Ok(entry.create_instance(&create_info, None)?) }
+}
+
+// Names of the instance extensions exposed by the loader and drivers, plus the ones
+// provided by the validation layers when validation is enabled.
+pub fn available_instance_extensions(
+    entry: &Entry,
+    validation: &ValidationInfo,
+) -> RendererResult<Vec<String>> {
+    let mut extension_properties = entry
+        .enumerate_instance_extension_properties()
+        .context("Failed to enumerate Instance Extensions Properties!")?;
+    if validation.is_enable {
+        for layer_name in validation.required_validation_layers.iter() {
+            let layer_name = CString::new(layer_name.as_str()).unwrap();
+            extension_properties
+                .extend(enumerate_layer_extension_properties(entry, &layer_name)?);
+        }
+    }
+    Ok(extension_properties
+        .iter()
+        .map(|extension| {
+            unsafe { CStr::from_ptr(extension.extension_name.as_ptr()) }
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect())
+}
+
+// ash only wraps the global query, layer provided extensions go through the raw entry point.
+fn enumerate_layer_extension_properties(
+    entry: &Entry,
+    layer_name: &CStr,
+) -> RendererResult<Vec<vk::ExtensionProperties>> {
+    let mut count = 0;
+    let result = unsafe {
+        entry.fp_v1_0().enumerate_instance_extension_properties(
+            layer_name.as_ptr(),
+            &mut count,
+            ptr::null_mut(),
+        )
+    };
+    if result != vk::Result::SUCCESS {
+        return Err(RendererError::from_vk(
+            "Failed to enumerate Layer Extensions Properties!",
+            result,
+        ));
+    }
+    let mut extension_properties = Vec::with_capacity(count as usize);
+    let result = unsafe {
+        entry.fp_v1_0().enumerate_instance_extension_properties(
+            layer_name.as_ptr(),
+            &mut count,
+            extension_properties.as_mut_ptr(),
+        )
+    };
+    if result != vk::Result::SUCCESS {
+        return Err(RendererError::from_vk(
+            "Failed to enumerate Layer Extensions Properties!",
+            result,
+        ));
+    }
+    unsafe {
+        extension_properties.set_len(count as usize);
+    }
+    Ok(extension_properties)
+}
+
+pub fn is_instance_extension_available(
+    entry: &Entry,
+    validation: &ValidationInfo,
+    extension_name: &CStr,
+) -> RendererResult<bool> {
+    let extension_name = extension_name.to_string_lossy();
+    Ok(available_instance_extensions(entry, validation)?
+        .iter()
+        .any(|available_extension| *available_extension == extension_name))
+}
+
+// Reports every missing extension at once instead of a bare ERROR_EXTENSION_NOT_PRESENT.
+fn check_instance_extension_support(
+    entry: &Entry,
+    validation: &ValidationInfo,
+    required_extensions: &[*const i8],
+) -> RendererResult<()> {
+    let available_extensions = available_instance_extensions(entry, validation)?;
+    let missing_extensions: Vec<String> = required_extensions
+        .iter()
+        .map(|&extension| unsafe { CStr::from_ptr(extension) }.to_string_lossy().into_owned())
+        .filter(|extension| !available_extensions.contains(extension))
+        .collect();
+    if missing_extensions.is_empty() {
+        Ok(())
+    } else {
+        Err(RendererError::MissingExtensions(missing_extensions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_api_version_drops_patch() {
+        assert_eq!(parse_api_version("1.0"), Some(vk_make_version!(1, 0, 0)));
+        assert_eq!(parse_api_version(" 1.2.189 "), Some(vk_make_version!(1, 2, 0)));
+        assert_eq!(parse_api_version("1"), None);
+        assert_eq!(parse_api_version("1.x"), None);
+        assert_eq!(parse_api_version("1.2.3.4"), None);
+    }
+
+    #[test]
+    fn format_version_round_trips() {
+        assert_eq!(format_version(vk_make_version!(1, 2, 189)), "1.2.189");
+        let version = vk_make_version!(1, 1, 0);
+        assert_eq!(parse_api_version(&format_version(version)), Some(version));
+    }
 }
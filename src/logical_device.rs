@@ -1,26 +1,36 @@
 use crate::queue::find_queue_families;
-use crate::surface;
 use ash::version::InstanceV1_0;
 use ash::vk;
 
-use crate::debug::VALIDATION;
-use crate::physical_device::physical_device_extensions;
+use crate::debug::{ValidationFeature, ValidationInfo};
+use crate::device_features::EnabledDeviceFeatures;
+use crate::error::{RendererError, RendererResult, VkResultExt};
+use crate::physical_device::{
+    is_device_extension_supported, missing_device_extensions, required_device_extensions,
+};
 use std::default::Default;
 use std::ffi::CString;
 use std::ptr;
-use std::collections::HashSet;
 pub fn create_logical_device(
     instance: &ash::Instance,
     physical_device: &vk::PhysicalDevice,
     surface: &vk::SurfaceKHR,
-) -> ash::Device {
-    let indices = find_queue_families(instance, physical_device, surface);
-    let unique_queue_families= indices.into_unique();
+    validation: &ValidationInfo,
+    enabled_features: &EnabledDeviceFeatures,
+) -> RendererResult<ash::Device> {
+    let required_extensions = required_device_extensions(*surface != vk::SurfaceKHR::null());
+    let missing_extensions =
+        missing_device_extensions(instance, *physical_device, required_extensions)?;
+    if !missing_extensions.is_empty() {
+        return Err(RendererError::MissingExtensions(missing_extensions));
+    }
+    let indices = find_queue_families(instance, physical_device, surface)?;
+    let unique_queue_families= indices.unique_families();
     let mut queue_create_infos :Vec<vk::DeviceQueueCreateInfo> = vec![];
     let queue_priority = 1.0f32;
-    for (_i, queue_family) in unique_queue_families.iter().enumerate() {
+    for queue_family in unique_queue_families.iter() {
         let queue_create_info = vk::DeviceQueueCreateInfo{
-            queue_family_index: queue_family.clone(),
+            queue_family_index: *queue_family,
             queue_count: 1,
             p_queue_priorities: &queue_priority,
             ..Default::default()
@@ -29,21 +39,36 @@ pub fn create_logical_device(
     }
 
 
-    let physical_device_features = vk::PhysicalDeviceFeatures {
-        ..Default::default()
-    };
-    let requred_validation_layer_raw_names: Vec<CString> = VALIDATION
+    let physical_device_features = enabled_features.physical_device_features();
+    let requred_validation_layer_raw_names: Vec<CString> = validation
         .required_validation_layers
         .iter()
-        .map(|layer_name| CString::new(*layer_name).unwrap())
+        .map(|layer_name| CString::new(layer_name.as_str()).unwrap())
         .collect();
     let enable_layer_names: Vec<*const i8> = requred_validation_layer_raw_names
         .iter()
         .map(|layer_name| layer_name.as_ptr())
         .collect();
-    let device_extensions_raw_names :Vec<CString> = physical_device_extensions.iter().map(|extension| {
+    let mut device_extensions_raw_names :Vec<CString> = required_extensions.iter().map(|extension| {
         CString::new(*extension).unwrap()
     }).collect();
+    for extension in enabled_features.extensions.iter() {
+        if !required_extensions.contains(&extension.as_str()) {
+            device_extensions_raw_names.push(CString::new(extension.as_str()).unwrap());
+        }
+    }
+    // debugPrintfEXT compiles to a non-semantic instruction set; drivers before 1.2 need this
+    // extension to accept such shaders
+    if validation.has_feature(ValidationFeature::DebugPrintf)
+        && !enabled_features.is_extension_enabled("VK_KHR_shader_non_semantic_info")
+        && is_device_extension_supported(
+            instance,
+            *physical_device,
+            "VK_KHR_shader_non_semantic_info",
+        )?
+    {
+        device_extensions_raw_names.push(CString::new("VK_KHR_shader_non_semantic_info").unwrap());
+    }
     let device_extensions_names :Vec<*const i8> = device_extensions_raw_names.iter().map(|extension| {
         extension.as_ptr()
     }).collect();
@@ -51,24 +76,24 @@ pub fn create_logical_device(
         queue_create_info_count: queue_create_infos.len() as u32,
         p_queue_create_infos: queue_create_infos.as_ptr(),
         p_enabled_features: &physical_device_features,
-        pp_enabled_layer_names: if VALIDATION.is_enable {
+        pp_enabled_layer_names: if validation.is_enable {
             enable_layer_names.as_ptr()
         } else {
             ptr::null()
         },
-        enabled_layer_count: if VALIDATION.is_enable {
+        enabled_layer_count: if validation.is_enable {
             enable_layer_names.len()
         } else {
             0
         } as u32,
-        enabled_extension_count :physical_device_extensions.len() as u32,
+        enabled_extension_count :device_extensions_names.len() as u32,
         pp_enabled_extension_names: device_extensions_names.as_ptr(),
         ..Default::default()
     };
     unsafe {
         instance
             .create_device(*physical_device, &device_create_info, None)
-            .expect("Failed to create logical device")
+            .context("Failed to create logical device")
     }
 }
 // pub struct SurfaceStuff {
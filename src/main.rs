@@ -2,6 +2,7 @@ mod core;
 mod instance;
 mod debug;
 mod physical_device;
+mod device_features;
 mod queue;
 mod logical_device; 
 mod surface;
@@ -9,9 +10,121 @@ mod swapchain;
 mod pipeline;
 mod framebuffer;
 mod command;
+mod context;
+mod memory;
+mod allocator;
+mod buffer;
+mod vertex;
+mod uniform;
+mod descriptor;
+mod push_constant;
+mod texture;
+mod model;
+mod headless;
+mod error;
+mod cleanup;
+mod config;
+mod report;
 use crate::core::Core;
+use crate::error::RendererResult;
+use crate::headless::HeadlessCore;
+use crate::config::CoreConfig;
+use crate::instance::format_version;
 
 fn main() {
-   Core::burn(Core::build("hello",winit::event_loop::EventLoop::new()));
+   // show validation warnings and errors unless RUST_LOG says otherwise
+   env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+   if std::env::args().any(|arg| arg == "--report") {
+      match print_report() {
+         Ok(()) => return,
+         Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+         }
+      }
+   }
+   if std::env::args().any(|arg| arg == "--headless") {
+      let (pixels, extent) = match render_headless() {
+         Ok(frame) => frame,
+         Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+         }
+      };
+      if let Err(err) = image::save_buffer(
+         "headless.png",
+         &pixels,
+         extent.width,
+         extent.height,
+         image::ColorType::RGBA(8),
+      ) {
+         eprintln!("Failed to save headless frame: {}", err);
+         std::process::exit(1);
+      }
+      return;
+   }
+   match Core::build(
+      "hello",
+      winit::event_loop::EventLoop::new(),
+      &core_config(),
+   ) {
+      Ok((mut core, event_loop)) => {
+         log::info!("Using Vulkan API version {}", format_version(core.api_version()));
+         log::info!("Enabled device features: {:?}", core.enabled_device_features());
+         log::info!("Queue families: {:?}", core.queue_family_indices());
+         // --model <path.obj> swaps the triangle for a model
+         if let Some(path) = std::env::args().skip_while(|arg| arg != "--model").nth(1) {
+            if let Err(err) = core.load_model(std::path::Path::new(&path)) {
+               eprintln!("{}", err);
+               std::process::exit(1);
+            }
+         }
+         // --texture <path> replaces the white default texture, or the model's
+         if let Some(path) = std::env::args().skip_while(|arg| arg != "--texture").nth(1) {
+            if let Err(err) = core.set_texture(std::path::Path::new(&path)) {
+               eprintln!("{}", err);
+               std::process::exit(1);
+            }
+         }
+         Core::burn((core, event_loop))
+      }
+      Err(err) => {
+         eprintln!("{}", err);
+         std::process::exit(1);
+      }
+   }
    
 }
+
+fn render_headless() -> RendererResult<(Vec<u8>, ash::vk::Extent2D)> {
+   let mut core = HeadlessCore::build(1024, 768, &core_config())?;
+   log::info!("Using Vulkan API version {}", format_version(core.api_version()));
+   log::info!("Enabled device features: {:?}", core.enabled_device_features());
+   log::info!("Queue families: {:?}", core.queue_family_indices());
+   core.burn_frame()?;
+   Ok((core.read_pixels()?, core.extent()))
+}
+
+// --report --headless skips the window, and with it the surface formats and present modes
+fn print_report() -> RendererResult<()> {
+   let config = core_config();
+   let report = if std::env::args().any(|arg| arg == "--headless") {
+      report::capability_report(&config, None)?
+   } else {
+      let event_loop = winit::event_loop::EventLoop::new();
+      let window = winit::window::WindowBuilder::new()
+         .with_title("report")
+         .with_visible(false)
+         .build(&event_loop)?;
+      report::capability_report(&config, Some(&window))?
+   };
+   println!("{}", serde_json::to_string_pretty(&report)?);
+   Ok(())
+}
+
+// the instance hands these to drivers and tools like RenderDoc
+fn core_config() -> CoreConfig {
+   CoreConfig::from_env()
+      .with_application("hello", ash::vk_make_version!(0, 1, 0))
+      .with_engine("Engine", ash::vk_make_version!(0, 1, 0))
+}
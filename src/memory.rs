@@ -0,0 +1,17 @@
+use ash::vk;
+
+use crate::error::{RendererError, RendererResult};
+
+pub fn find_memory_type(
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    type_filter: u32,
+    required_properties: vk::MemoryPropertyFlags,
+) -> RendererResult<u32> {
+    for (i, memory_type) in memory_properties.memory_types.iter().enumerate() {
+        if (type_filter & (1 << i)) > 0 && memory_type.property_flags.contains(required_properties)
+        {
+            return Ok(i as u32);
+        }
+    }
+    Err(RendererError::NoSuitableMemoryType)
+}
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::{RendererError, RendererResult};
+use crate::vertex::Vertex;
+
+// Every model of an OBJ file merged into one indexed mesh, drawn by Core::load_model.
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    // resolved against the directory of the .obj file
+    pub diffuse_texture: Option<PathBuf>,
+}
+
+// Loads an .obj and the .mtl files it references. Vertices are colored with the material's
+// diffuse color and UVs are flipped to Vulkan's top left origin.
+pub fn load_obj_model(path: &Path) -> RendererResult<MeshData> {
+    let (models, materials) = tobj::load_obj(path).map_err(|source| RendererError::ModelLoad {
+        path: path.display().to_string(),
+        source,
+    })?;
+
+    let (vertices, indices) = merge_models(&models, &materials);
+
+    // a single texture is bound per draw, so the first material that has one wins
+    let diffuse_textures: Vec<&str> = materials
+        .iter()
+        .filter(|material| !material.diffuse_texture.is_empty())
+        .map(|material| material.diffuse_texture.as_str())
+        .collect();
+    let diffuse_texture = diffuse_textures.first().map(|texture| {
+        path.parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(texture.replace('\\', "/"))
+    });
+    if diffuse_textures.iter().any(|&texture| texture != diffuse_textures[0]) {
+        log::warn!(
+            "{} uses several diffuse textures, only {} is bound",
+            path.display(),
+            diffuse_textures[0]
+        );
+    }
+
+    log::info!(
+        "Loaded {}: {} models, {} unique vertices, {} indices",
+        path.display(),
+        models.len(),
+        vertices.len(),
+        indices.len()
+    );
+    Ok(MeshData {
+        vertices,
+        indices,
+        diffuse_texture,
+    })
+}
+
+// Flattens every model into one vertex list, identical vertices are shared even
+// when they come from different models.
+fn merge_models(models: &[tobj::Model], materials: &[tobj::Material]) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    let mut unique_vertices: HashMap<[u32; 11], u32> = HashMap::new();
+    for model in models.iter() {
+        let mesh = &model.mesh;
+        let color = match mesh.material_id.and_then(|id| materials.get(id)) {
+            Some(material) => material.diffuse,
+            None => [1.0, 1.0, 1.0],
+        };
+        for &index in mesh.indices.iter() {
+            let i = index as usize;
+            let vertex = Vertex {
+                pos: [
+                    mesh.positions[3 * i],
+                    mesh.positions[3 * i + 1],
+                    mesh.positions[3 * i + 2],
+                ],
+                color,
+                tex_coord: if mesh.texcoords.is_empty() {
+                    [0.0, 0.0]
+                } else {
+                    [mesh.texcoords[2 * i], 1.0 - mesh.texcoords[2 * i + 1]]
+                },
+                normal: if mesh.normals.is_empty() {
+                    [0.0, 0.0, 0.0]
+                } else {
+                    [
+                        mesh.normals[3 * i],
+                        mesh.normals[3 * i + 1],
+                        mesh.normals[3 * i + 2],
+                    ]
+                },
+            };
+            let vertex_index = *unique_vertices
+                .entry(vertex_key(&vertex))
+                .or_insert_with(|| {
+                    vertices.push(vertex);
+                    (vertices.len() - 1) as u32
+                });
+            indices.push(vertex_index);
+        }
+    }
+    (vertices, indices)
+}
+
+// f32 isn't Hash/Eq, the bit patterns are enough to spot exact duplicates
+fn vertex_key(vertex: &Vertex) -> [u32; 11] {
+    let mut key = [0; 11];
+    let components = vertex
+        .pos
+        .iter()
+        .chain(vertex.color.iter())
+        .chain(vertex.tex_coord.iter())
+        .chain(vertex.normal.iter());
+    for (slot, component) in key.iter_mut().zip(components) {
+        *slot = component.to_bits();
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(positions: Vec<f32>, indices: Vec<u32>) -> tobj::Model {
+        tobj::Model {
+            mesh: tobj::Mesh {
+                positions,
+                normals: vec![],
+                texcoords: vec![],
+                indices,
+                material_id: None,
+            },
+            name: String::new(),
+        }
+    }
+
+    #[test]
+    fn shares_duplicate_vertices_across_models() {
+        // two triangles of a quad, split into separate models along the diagonal
+        let first = model(vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0], vec![0, 1, 2]);
+        let second = model(vec![0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0], vec![0, 1, 2]);
+        let (vertices, indices) = merge_models(&[first, second], &[]);
+        assert_eq!(vertices.len(), 4);
+        assert_eq!(indices, vec![0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn vertex_key_only_matches_identical_vertices() {
+        let vertex = Vertex {
+            pos: [0.0, 0.0, 0.0],
+            color: [1.0, 1.0, 1.0],
+            tex_coord: [0.0, 0.0],
+            normal: [0.0, 0.0, 1.0],
+        };
+        let same = vertex;
+        assert_eq!(vertex_key(&vertex), vertex_key(&same));
+        let mut moved = vertex;
+        moved.tex_coord = [0.5, 0.0];
+        assert_ne!(vertex_key(&vertex), vertex_key(&moved));
+        let mut flipped = vertex;
+        flipped.normal = [0.0, 0.0, -1.0];
+        assert_ne!(vertex_key(&vertex), vertex_key(&flipped));
+    }
+}
@@ -1,54 +1,192 @@
-use crate::queue::{find_queue_families, QueueFamilyIndices};
+use crate::device_features::DeviceRequirements;
+use crate::queue::find_queue_families;
 use crate::surface::SurfaceStuff;
-use crate::swapchain::{query_swapchain_support, SwapChainSupportDetails};
+use crate::swapchain::query_swapchain_support;
 use ash::version::InstanceV1_0;
 use ash::vk;
-use std::default::Default;
 use std::ffi::CStr;
 
-pub const physical_device_extensions: [&'static str; 1] = ["VK_KHR_swapchain"];
+use crate::error::{RendererError, RendererResult, VkResultExt};
+
+pub const PHYSICAL_DEVICE_EXTENSIONS: [&str; 1] = ["VK_KHR_swapchain"];
+
+// Only presenting needs VK_KHR_swapchain, headless devices may not expose WSI at all.
+pub fn required_device_extensions(has_surface: bool) -> &'static [&'static str] {
+    if has_surface {
+        &PHYSICAL_DEVICE_EXTENSIONS
+    } else {
+        &[]
+    }
+}
+
+// Which physical device to use when several are suitable.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceSelection {
+    // highest score wins
+    Best,
+    // case-insensitive substring of the device name
+    Name(String),
+    // position in vkEnumeratePhysicalDevices order
+    Index(usize),
+    PipelineCacheUuid([u8; vk::UUID_SIZE]),
+    // score CPU implementations (e.g. lavapipe, SwiftShader) above real GPUs
+    PreferCpu,
+}
+
+impl DeviceSelection {
+    // Reads VULKAN_DEVICE: unset or empty = best, "cpu", "index:<n>",
+    // "uuid:<32 hex digits>", anything else is a device name substring.
+    pub fn from_env() -> Self {
+        match std::env::var("VULKAN_DEVICE") {
+            Ok(value) => DeviceSelection::parse(&value),
+            Err(_) => DeviceSelection::Best,
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        let value = value.trim();
+        if value.is_empty() {
+            DeviceSelection::Best
+        } else if value.eq_ignore_ascii_case("cpu") {
+            DeviceSelection::PreferCpu
+        } else if let Some(index) = value.strip_prefix("index:") {
+            match index.trim().parse() {
+                Ok(index) => DeviceSelection::Index(index),
+                Err(_) => DeviceSelection::Name(value.to_string()),
+            }
+        } else if let Some(uuid) = value.strip_prefix("uuid:") {
+            match parse_uuid(uuid) {
+                Some(uuid) => DeviceSelection::PipelineCacheUuid(uuid),
+                None => DeviceSelection::Name(value.to_string()),
+            }
+        } else {
+            DeviceSelection::Name(value.to_string())
+        }
+    }
+
+    fn matches(&self, index: usize, properties: &vk::PhysicalDeviceProperties) -> bool {
+        match self {
+            DeviceSelection::Best | DeviceSelection::PreferCpu => true,
+            DeviceSelection::Name(name) => device_name(properties)
+                .to_lowercase()
+                .contains(&name.to_lowercase()),
+            DeviceSelection::Index(selected_index) => *selected_index == index,
+            DeviceSelection::PipelineCacheUuid(uuid) => properties.pipeline_cache_uuid == *uuid,
+        }
+    }
+}
+
+fn parse_uuid(value: &str) -> Option<[u8; vk::UUID_SIZE]> {
+    let digits: Vec<char> = value.chars().filter(|c| *c != '-').collect();
+    if digits.len() != vk::UUID_SIZE * 2 {
+        return None;
+    }
+    let mut uuid = [0u8; vk::UUID_SIZE];
+    for (i, byte) in uuid.iter_mut().enumerate() {
+        let pair: String = digits[i * 2..i * 2 + 2].iter().collect();
+        *byte = u8::from_str_radix(&pair, 16).ok()?;
+    }
+    Some(uuid)
+}
+
+pub fn device_name(properties: &vk::PhysicalDeviceProperties) -> String {
+    unsafe { CStr::from_ptr(properties.device_name.as_ptr()) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+// Device type dominates, limits and device local memory break ties within a type.
+pub fn rate_device(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    selection: &DeviceSelection,
+) -> u64 {
+    let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+    let memory_properties =
+        unsafe { instance.get_physical_device_memory_properties(physical_device) };
+
+    let type_score: u64 = match properties.device_type {
+        vk::PhysicalDeviceType::CPU if *selection == DeviceSelection::PreferCpu => 8_000_000,
+        vk::PhysicalDeviceType::DISCRETE_GPU => 4_000_000,
+        vk::PhysicalDeviceType::INTEGRATED_GPU => 3_000_000,
+        vk::PhysicalDeviceType::VIRTUAL_GPU => 2_000_000,
+        vk::PhysicalDeviceType::CPU => 1_000_000,
+        _ => 0,
+    };
+    let limits_score = properties.limits.max_image_dimension2_d as u64;
+    let device_local_mib: u64 = memory_properties.memory_heaps
+        [..memory_properties.memory_heap_count as usize]
+        .iter()
+        .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+        .map(|heap| heap.size / (1024 * 1024))
+        .sum();
+
+    type_score + limits_score + device_local_mib / 64
+}
 
 pub fn pick_pyhsical_device(
     instance: &ash::Instance,
-    surface: &SurfaceStuff,
-) -> vk::PhysicalDevice {
+    surface: Option<&SurfaceStuff>,
+    selection: &DeviceSelection,
+    requirements: &DeviceRequirements,
+) -> RendererResult<vk::PhysicalDevice> {
     let physical_devices = unsafe {
         instance
             .enumerate_physical_devices()
-            .expect("Failed to enumerate physical devices.")
+            .context("Failed to enumerate physical devices.")?
     };
-    // for physical_device in physical_devices.iter() {
-    //     let physical_device_properties =
-    //     unsafe { instance.get_physical_device_properties(*physical_device) };
-    //     unsafe{   println!("{:?}", std::ffi::CStr::from_ptr(physical_device_properties.device_name.as_ptr()));}   
-    //     if(  unsafe{  std::ffi::CStr::from_ptr(physical_device_properties.device_name.as_ptr()).to_str().unwrap()} == "Intel(R) UHD Graphics 630")
-    //     {
-    //         return *physical_device;
-    //     }
-    // }
-    for physical_device in physical_devices.iter() {
-
-        if is_device_suitable(instance, *physical_device, surface) {
-            return *physical_device;
+    let mut best_device: Option<(u64, vk::PhysicalDevice)> = None;
+    for (index, &physical_device) in physical_devices.iter().enumerate() {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        if !selection.matches(index, &properties)
+            || !is_device_suitable(instance, physical_device, surface, requirements)?
+        {
+            continue;
+        }
+        let score = rate_device(instance, physical_device, selection);
+        log::info!("Physical Device {}: {} (score {})", index, device_name(&properties), score);
+        // strictly greater keeps the first device on ties, so the pick is deterministic
+        let is_better = match best_device {
+            Some((best_score, _)) => score > best_score,
+            None => true,
+        };
+        if is_better {
+            best_device = Some((score, physical_device));
         }
-      
     }
-    panic!("Failed to find a suitable GPU!");
+    best_device
+        .map(|(_, physical_device)| physical_device)
+        .ok_or(RendererError::NoSuitableGpu)
 }
 fn is_device_suitable(
     instance: &ash::Instance,
     physical_device: vk::PhysicalDevice,
-    surface: &SurfaceStuff,
-) -> bool {
-    let physical_device_properties =
-        unsafe { instance.get_physical_device_properties(physical_device) };
-    let physical_device_features =
-        unsafe { instance.get_physical_device_features(physical_device) };
-        if check_device_suitable(instance, physical_device) &&find_queue_families(instance, &physical_device, &surface.surface).is_complete(){
-                let swap_chain_support = query_swapchain_support(physical_device, surface);
-                return swap_chain_support.formats.len() > 0 && swap_chain_support.present_modes.len() > 0
+    surface: Option<&SurfaceStuff>,
+    requirements: &DeviceRequirements,
+) -> RendererResult<bool> {
+    let surface_handle = surface.map_or(vk::SurfaceKHR::null(), |surface| surface.surface);
+    let missing_requirements = requirements.missing_requirements(instance, physical_device)?;
+    if !missing_requirements.is_empty() {
+        let physical_device_properties =
+            unsafe { instance.get_physical_device_properties(physical_device) };
+        log::info!(
+            "{} is missing required device features: {}",
+            device_name(&physical_device_properties),
+            missing_requirements.join(", ")
+        );
+        return Ok(false);
+    }
+        if check_device_suitable(instance, physical_device, surface.is_some())? &&find_queue_families(instance, &physical_device, &surface_handle)?.is_complete(){
+                return match surface {
+                    Some(surface) => {
+                        let swap_chain_support = query_swapchain_support(physical_device, surface)?;
+                        Ok(!swap_chain_support.formats.is_empty() && !swap_chain_support.present_modes.is_empty())
+                    }
+                    // headless rendering only needs a graphics queue
+                    None => Ok(true),
+                };
             }
-        false
+        Ok(false)
     
 
 }
@@ -56,25 +194,105 @@ fn is_device_suitable(
 fn check_device_suitable(
     instance: &ash::Instance,
     physical_device: vk::PhysicalDevice,
-) -> bool {
-    let physical_device_available_extensions =
-        unsafe { instance.enumerate_device_extension_properties(physical_device) };
-    match physical_device_available_extensions {
-        Ok(available_extensions) => {
-            for required_extension in physical_device_extensions.iter(){
-                if ! (available_extensions.iter().any(|extension| {
-                   unsafe{ CStr::from_ptr(extension.extension_name.as_ptr())}
-                        .to_str()
-                        .unwrap()
-                        == *required_extension})){
-                    return false;
-                        }
-                
-            } 
-            true
-        }
-        Err(err) => {
-            panic!("Failed to enumerate device extensions: {:?}", err);
+    has_surface: bool,
+) -> RendererResult<bool> {
+    let missing_extensions = missing_device_extensions(
+        instance,
+        physical_device,
+        required_device_extensions(has_surface),
+    )?;
+    if !missing_extensions.is_empty() {
+        let physical_device_properties =
+            unsafe { instance.get_physical_device_properties(physical_device) };
+        log::info!(
+            "{} is missing device extensions: {}",
+            device_name(&physical_device_properties),
+            missing_extensions.join(", ")
+        );
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+pub fn missing_device_extensions(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    extension_names: &[&str],
+) -> RendererResult<Vec<String>> {
+    let mut missing_extensions = vec![];
+    for extension_name in extension_names.iter() {
+        if !is_device_extension_supported(instance, physical_device, extension_name)? {
+            missing_extensions.push(extension_name.to_string());
         }
     }
+    Ok(missing_extensions)
+}
+
+pub fn is_device_extension_supported(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    extension_name: &str,
+) -> RendererResult<bool> {
+    let available_extensions = unsafe {
+        instance
+            .enumerate_device_extension_properties(physical_device)
+            .context("Failed to enumerate device extensions")?
+    };
+    Ok(available_extensions.iter().any(|extension| {
+        unsafe { CStr::from_ptr(extension.extension_name.as_ptr()) }.to_string_lossy()
+            == extension_name
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_device_selection() {
+        assert_eq!(DeviceSelection::parse(""), DeviceSelection::Best);
+        assert_eq!(DeviceSelection::parse("  "), DeviceSelection::Best);
+        assert_eq!(DeviceSelection::parse("CPU"), DeviceSelection::PreferCpu);
+        assert_eq!(DeviceSelection::parse("index:2"), DeviceSelection::Index(2));
+        assert_eq!(DeviceSelection::parse("index: 1 "), DeviceSelection::Index(1));
+        assert_eq!(
+            DeviceSelection::parse("GeForce"),
+            DeviceSelection::Name("GeForce".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_device_selection_falls_back_to_name() {
+        assert_eq!(
+            DeviceSelection::parse("index:first"),
+            DeviceSelection::Name("index:first".to_string())
+        );
+        assert_eq!(
+            DeviceSelection::parse("uuid:1234"),
+            DeviceSelection::Name("uuid:1234".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_device_selection_uuid() {
+        let mut uuid = [0u8; vk::UUID_SIZE];
+        uuid[0] = 0x01;
+        uuid[15] = 0xff;
+        assert_eq!(
+            DeviceSelection::parse("uuid:01000000-0000-0000-0000-0000000000ff"),
+            DeviceSelection::PipelineCacheUuid(uuid)
+        );
+    }
+
+    #[test]
+    fn parse_uuid_digits() {
+        assert_eq!(
+            parse_uuid("000102030405060708090a0b0c0d0e0F"),
+            Some([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15])
+        );
+        // too short, too long, not hex
+        assert_eq!(parse_uuid("000102030405060708090a0b0c0d0e"), None);
+        assert_eq!(parse_uuid("000102030405060708090a0b0c0d0e0f00"), None);
+        assert_eq!(parse_uuid("zz0102030405060708090a0b0c0d0e0f"), None);
+    }
 }
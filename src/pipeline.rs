@@ -1,11 +1,16 @@
 use ash::version::DeviceV1_0;
 use ash::vk;
+use std::ffi::CString;
 use std::ptr;
 use std::{fs::File, io::Read};
+
+use crate::error::{RendererError, RendererResult, VkResultExt};
+use crate::vertex::Vertex;
 pub fn create_render_pass(
     device: &ash::Device,
     swap_chain_image_format: vk::Format,
-) -> vk::RenderPass {
+    final_layout: vk::ImageLayout,
+) -> RendererResult<vk::RenderPass> {
     let color_attachment = vk::AttachmentDescription {
         format: swap_chain_image_format,
         samples: vk::SampleCountFlags::TYPE_1,
@@ -14,13 +19,12 @@ pub fn create_render_pass(
         stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
         stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
         initial_layout: vk::ImageLayout::UNDEFINED,
-        final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+        final_layout,
         ..Default::default()
     };
     let color_attachment_ref = vk::AttachmentReference {
         attachment: 0,
         layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-        ..Default::default()
     };
     let subpass = vk::SubpassDescription {
         pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
@@ -28,7 +32,7 @@ pub fn create_render_pass(
         color_attachment_count:1,
         ..Default::default()
     };
-    let subpass_dependency = vk::SubpassDependency {
+    let mut subpass_dependencies = vec![vk::SubpassDependency {
         src_subpass: vk::SUBPASS_EXTERNAL,
         dst_subpass: 0,
         src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
@@ -36,76 +40,95 @@ pub fn create_render_pass(
         dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
         dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
         ..Default::default()
-    };
+    }];
+    // an offscreen target is copied out afterwards, so the copy has to wait for the writes
+    if final_layout == vk::ImageLayout::TRANSFER_SRC_OPTIMAL {
+        subpass_dependencies.push(vk::SubpassDependency {
+            src_subpass: 0,
+            dst_subpass: vk::SUBPASS_EXTERNAL,
+            src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            dst_stage_mask: vk::PipelineStageFlags::TRANSFER,
+            dst_access_mask: vk::AccessFlags::TRANSFER_READ,
+            ..Default::default()
+        });
+    }
     let create_info = vk::RenderPassCreateInfo {
         attachment_count: 1,
         p_attachments: &color_attachment,
         subpass_count: 1,
         p_subpasses: &subpass,
-        dependency_count: 1,
-        p_dependencies: &subpass_dependency,
+        dependency_count: subpass_dependencies.len() as u32,
+        p_dependencies: subpass_dependencies.as_ptr(),
         ..Default::default()
     };
     unsafe {
         device
             .create_render_pass(&create_info, None)
-            .expect("Failed to create render pass")
+            .context("Failed to create render pass")
     }
 }
 
+// The viewport and scissor are dynamic and set while recording, so the pipeline
+// survives a swapchain resize.
 pub fn create_graphic_pipeline(
     device: &ash::Device,
-    swap_chain_extent: &vk::Extent2D,
     render_pass: &vk::RenderPass,
-) -> (vk::Pipeline, vk::PipelineLayout) {
-    let vert_shader_code = read_shader("src/shaders/shader.vert.spv");
-    let frag_shader_code = read_shader("src/shaders/shader.frag.spv");
+    descriptor_set_layout: &vk::DescriptorSetLayout,
+    push_constant_ranges: &[vk::PushConstantRange],
+) -> RendererResult<(vk::Pipeline, vk::PipelineLayout)> {
+    let vert_shader_code = read_shader("src/shaders/shader.vert.spv")?;
+    let frag_shader_code = read_shader("src/shaders/shader.frag.spv")?;
 
-    let vert_shader_module = create_shader_module(device, &vert_shader_code[..]);
-    let frag_shader_module = create_shader_module(device, &frag_shader_code[..]);
+    let main_function_name = CString::new("main").unwrap();
+
+    let vert_shader_module = create_shader_module(device, &vert_shader_code[..])?;
+    let frag_shader_module = match create_shader_module(device, &frag_shader_code[..]) {
+        Ok(frag_shader_module) => frag_shader_module,
+        Err(err) => {
+            unsafe { device.destroy_shader_module(vert_shader_module, None) };
+            return Err(err);
+        }
+    };
     let vert_shader_stage_info = vk::PipelineShaderStageCreateInfo {
         module: vert_shader_module,
-        p_name: b"main\0".as_ptr() as *const _,
+        p_name: main_function_name.as_ptr(),
         stage: vk::ShaderStageFlags::VERTEX,
         ..Default::default()
     };
     let frag_shader_stage_info = vk::PipelineShaderStageCreateInfo {
         module: frag_shader_module,
-        p_name: b"main\0".as_ptr() as *const _,
+        p_name: main_function_name.as_ptr(),
         stage: vk::ShaderStageFlags::FRAGMENT,
         ..Default::default()
     };
     let shader_stages = 
     [vert_shader_stage_info, frag_shader_stage_info];
 
+    let binding_descriptions = Vertex::get_binding_description();
+    let attribute_descriptions = Vertex::get_attribute_descriptions();
     let vertex_input_info = vk::PipelineVertexInputStateCreateInfo {
-        vertex_binding_description_count: 0,
-        p_vertex_binding_descriptions: ptr::null(),
-        vertex_attribute_description_count: 0,
-        p_vertex_attribute_descriptions: ptr::null(),
+        vertex_binding_description_count: binding_descriptions.len() as u32,
+        p_vertex_binding_descriptions: binding_descriptions.as_ptr(),
+        vertex_attribute_description_count: attribute_descriptions.len() as u32,
+        p_vertex_attribute_descriptions: attribute_descriptions.as_ptr(),
         ..Default::default()
     };
     let input_assembly_info = vk::PipelineInputAssemblyStateCreateInfo {
         topology: vk::PrimitiveTopology::TRIANGLE_LIST,
         ..Default::default()
     };
-    let viewport = vk::Viewport {
-        x: 0.0,
-        y: 0.0,
-        width: swap_chain_extent.width as f32,
-        height: swap_chain_extent.height as f32,
-        min_depth: 0.0,
-        max_depth: 1.0,
-    };
-    let scissor = vk::Rect2D {
-        offset: vk::Offset2D { x: 0, y: 0 },
-        extent: *swap_chain_extent,
-    };
     let viewport_state = vk::PipelineViewportStateCreateInfo {
         viewport_count: 1,
-        p_viewports: &viewport,
+        p_viewports: ptr::null(),
         scissor_count: 1,
-        p_scissors: &scissor,
+        p_scissors: ptr::null(),
+        ..Default::default()
+    };
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    let dynamic_state_info = vk::PipelineDynamicStateCreateInfo {
+        dynamic_state_count: dynamic_states.len() as u32,
+        p_dynamic_states: dynamic_states.as_ptr(),
         ..Default::default()
     };
     let rasterizer = vk::PipelineRasterizationStateCreateInfo {
@@ -114,7 +137,7 @@ pub fn create_graphic_pipeline(
         polygon_mode: vk::PolygonMode::FILL,
         line_width: 1.0,
         cull_mode: vk::CullModeFlags::BACK,
-        front_face: vk::FrontFace::CLOCKWISE,
+        front_face: vk::FrontFace::COUNTER_CLOCKWISE,
         depth_bias_enable: vk::FALSE,
         depth_bias_constant_factor: 0.0,
         depth_bias_clamp: 0.0,
@@ -139,24 +162,34 @@ pub fn create_graphic_pipeline(
         src_alpha_blend_factor: vk::BlendFactor::ONE,
         dst_alpha_blend_factor: vk::BlendFactor::ZERO,
         alpha_blend_op: vk::BlendOp::ADD,
-        ..Default::default()
     };
     let color_blend_info = vk::PipelineColorBlendStateCreateInfo {
         attachment_count: 1,
         p_attachments: &color_blend_attachment,
         ..Default::default()
     };
+    let set_layouts = [*descriptor_set_layout];
     let pipeline_layout_info = vk::PipelineLayoutCreateInfo {
-        set_layout_count: 0,
-        p_set_layouts: ptr::null(),
-        push_constant_range_count: 0,
-        p_push_constant_ranges: ptr::null(),
+        set_layout_count: set_layouts.len() as u32,
+        p_set_layouts: set_layouts.as_ptr(),
+        push_constant_range_count: push_constant_ranges.len() as u32,
+        p_push_constant_ranges: push_constant_ranges.as_ptr(),
         ..Default::default()
     };
     let pipeline_layout = unsafe {
         device
             .create_pipeline_layout(&pipeline_layout_info, None)
-            .expect("Failed to create pipeline layout!")
+            .context("Failed to create pipeline layout!")
+    };
+    let pipeline_layout = match pipeline_layout {
+        Ok(pipeline_layout) => pipeline_layout,
+        Err(err) => {
+            unsafe {
+                device.destroy_shader_module(vert_shader_module, None);
+                device.destroy_shader_module(frag_shader_module, None);
+            }
+            return Err(err);
+        }
     };
     let create_info = vk::GraphicsPipelineCreateInfo {
         stage_count: 2,
@@ -167,7 +200,7 @@ pub fn create_graphic_pipeline(
         p_rasterization_state: &rasterizer,
         p_multisample_state: &multisampling,
         p_color_blend_state: &color_blend_info,
-        p_dynamic_state: ptr::null(),
+        p_dynamic_state: &dynamic_state_info,
         p_depth_stencil_state: ptr::null(),
         layout: pipeline_layout,
         render_pass: *render_pass,
@@ -176,27 +209,42 @@ pub fn create_graphic_pipeline(
         base_pipeline_index: -1,
         ..Default::default()
     };
-    let graphics_pipeline = unsafe {
-        device
-            .create_graphics_pipelines(vk::PipelineCache::null(), &[create_info], None)
-            .expect("Failed to create graphics pipeline!")
-            .pop()
-            .unwrap()
+    let graphics_pipelines = unsafe {
+        device.create_graphics_pipelines(vk::PipelineCache::null(), &[create_info], None)
     };
     unsafe {
         device.destroy_shader_module(vert_shader_module, None);
         device.destroy_shader_module(frag_shader_module, None);
     };
-    (graphics_pipeline, pipeline_layout)
+    match graphics_pipelines {
+        Ok(mut graphics_pipelines) => Ok((graphics_pipelines.pop().unwrap(), pipeline_layout)),
+        Err((graphics_pipelines, result)) => {
+            // the pipelines that failed are null, destroying those is a no-op
+            unsafe {
+                for graphics_pipeline in graphics_pipelines {
+                    device.destroy_pipeline(graphics_pipeline, None);
+                }
+                device.destroy_pipeline_layout(pipeline_layout, None);
+            }
+            Err(RendererError::from_vk(
+                "Failed to create graphics pipeline!",
+                result,
+            ))
+        }
+    }
 }
-fn read_shader(filename: &str) -> Vec<u8> {
-    let mut file = File::open(filename).expect("Failed to open shader file");
+fn read_shader(filename: &str) -> RendererResult<Vec<u8>> {
+    let shader_io_error = |source| RendererError::ShaderIo {
+        path: filename.to_string(),
+        source,
+    };
+    let mut file = File::open(filename).map_err(shader_io_error)?;
     let mut contents: Vec<u8> = vec![];
     file.read_to_end(&mut contents)
-        .expect("Failed to read shader file");
-    contents
+        .map_err(shader_io_error)?;
+    Ok(contents)
 }
-fn create_shader_module(device: &ash::Device, code: &[u8]) -> vk::ShaderModule {
+fn create_shader_module(device: &ash::Device, code: &[u8]) -> RendererResult<vk::ShaderModule> {
     let shader_module_create_info = vk::ShaderModuleCreateInfo {
         code_size: code.len(),
         p_code: code.as_ptr() as *const u32,
@@ -205,6 +253,6 @@ fn create_shader_module(device: &ash::Device, code: &[u8]) -> vk::ShaderModule {
     unsafe {
         device
             .create_shader_module(&shader_module_create_info, None)
-            .expect("Failed to create shader module")
+            .context("Failed to create shader module")
     }
 }
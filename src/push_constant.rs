@@ -0,0 +1,77 @@
+use ash::version::{DeviceV1_0, InstanceV1_0};
+use ash::vk;
+
+use crate::error::{RendererError, RendererResult};
+
+// matches the push_constant block in shader.vert
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PushConstants {
+    pub tint: [f32; 4],
+    pub time: f32,
+    pub object_index: u32,
+}
+
+impl Default for PushConstants {
+    fn default() -> Self {
+        PushConstants {
+            tint: [1.0, 1.0, 1.0, 1.0],
+            time: 0.0,
+            object_index: 0,
+        }
+    }
+}
+
+// A range covering all of T, starting at `offset`.
+pub fn push_constant_range<T: Copy>(
+    stage_flags: vk::ShaderStageFlags,
+    offset: u32,
+) -> vk::PushConstantRange {
+    vk::PushConstantRange {
+        stage_flags,
+        offset,
+        size: std::mem::size_of::<T>() as u32,
+    }
+}
+
+// Every range has to end within maxPushConstantsSize, which is only guaranteed to be 128 bytes.
+pub fn check_push_constant_ranges(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    ranges: &[vk::PushConstantRange],
+) -> RendererResult<()> {
+    let limits = unsafe { instance.get_physical_device_properties(physical_device) }.limits;
+    for range in ranges.iter() {
+        let size = range.offset + range.size;
+        if size > limits.max_push_constants_size {
+            return Err(RendererError::PushConstantsTooLarge {
+                size,
+                max: limits.max_push_constants_size,
+            });
+        }
+    }
+    Ok(())
+}
+
+// Records vkCmdPushConstants for `data` into `range`, which must have been created for T.
+pub fn cmd_push_constants<T: Copy>(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    pipeline_layout: vk::PipelineLayout,
+    range: &vk::PushConstantRange,
+    data: &T,
+) {
+    debug_assert_eq!(range.size as usize, std::mem::size_of::<T>());
+    let bytes = unsafe {
+        std::slice::from_raw_parts(data as *const T as *const u8, std::mem::size_of::<T>())
+    };
+    unsafe {
+        device.cmd_push_constants(
+            command_buffer,
+            pipeline_layout,
+            range.stage_flags,
+            range.offset,
+            bytes,
+        );
+    }
+}
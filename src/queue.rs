@@ -2,30 +2,36 @@ use ash::version::InstanceV1_0;
 use ash::vk;
 use std::default::Default;
 use std::collections::HashSet;
+
+use crate::error::RendererResult;
+#[derive(Debug, Default)]
 pub struct QueueFamilyIndices {
     pub graphics_family: Option<u32>,
     pub present_family: Option<u32>,
+    // transfer without graphics or compute, usually backed by a DMA engine
+    pub transfer_family: Option<u32>,
+    // compute without graphics, for async compute
+    pub compute_family: Option<u32>,
 }
 impl QueueFamilyIndices {
     pub fn is_complete(&self) -> bool {
         self.graphics_family.is_some() && self.present_family.is_some()
     }
     pub fn iter(&self) -> impl Iterator<Item = u32> {
-        vec![self.graphics_family.unwrap(), self.present_family.unwrap()].into_iter()
+        let mut families = vec![self.graphics_family.unwrap(), self.present_family.unwrap()];
+        families.extend(self.transfer_family);
+        families.extend(self.compute_family);
+        families.into_iter()
     }
-    pub fn into_unique(&self) -> HashSet<u32> {
-        let mut unique_queue_families = HashSet::new();
-        unique_queue_families.insert(self.graphics_family.unwrap());
-        unique_queue_families.insert(self.present_family.unwrap());
-        unique_queue_families
+    pub fn unique_families(&self) -> HashSet<u32> {
+        self.iter().collect()
     }
-}
-impl Default for QueueFamilyIndices {
-    fn default() -> Self {
-        QueueFamilyIndices {
-            graphics_family: None,
-            present_family: None,
-        }
+    // every graphics family supports transfer, so it stands in when there is no dedicated one
+    pub fn transfer_family_or_graphics(&self) -> u32 {
+        self.transfer_family.unwrap_or(self.graphics_family.unwrap())
+    }
+    pub fn compute_family_or_graphics(&self) -> u32 {
+        self.compute_family.unwrap_or(self.graphics_family.unwrap())
     }
 }
 
@@ -33,8 +39,8 @@ pub fn find_queue_families(
     instance: &ash::Instance,
     physical_device: &vk::PhysicalDevice,
     surface: &vk::SurfaceKHR,
-) -> QueueFamilyIndices {
-    let entry = ash::Entry::new().unwrap();
+) -> RendererResult<QueueFamilyIndices> {
+    let entry = ash::Entry::new()?;
     let surface_loader = ash::extensions::khr::Surface::new(&entry,instance);
     let mut indices = QueueFamilyIndices {
         ..Default::default()
@@ -42,17 +48,38 @@ pub fn find_queue_families(
     let queue_families =
         unsafe { instance.get_physical_device_queue_family_properties(*physical_device) };
 
+        // first match wins for every family, the present family prefers to share the graphics one
         for (i,queue_family) in queue_families.iter().enumerate(){
-            if queue_family.queue_count > 0 && queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS) {
+            if queue_family.queue_count == 0 {
+                continue;
+            }
+            let flags = queue_family.queue_flags;
+            if indices.graphics_family.is_none() && flags.contains(vk::QueueFlags::GRAPHICS) {
                 indices.graphics_family = Some(i as u32);
             }
-            let is_present_support = unsafe {surface_loader.get_physical_device_surface_support(*physical_device,i as u32,*surface)};
-            if is_present_support {
+            // without a surface nothing is presented, the graphics family stands in for it
+            let is_present_support = if *surface == vk::SurfaceKHR::null() {
+                indices.graphics_family == Some(i as u32)
+            } else {
+                unsafe {surface_loader.get_physical_device_surface_support(*physical_device,i as u32,*surface)}
+            };
+            if is_present_support
+                && (indices.present_family.is_none() || indices.graphics_family == Some(i as u32))
+            {
                 indices.present_family = Some(i as u32);
             }
-            if indices.is_complete() {
-                break;
+            if indices.transfer_family.is_none()
+                && flags.contains(vk::QueueFlags::TRANSFER)
+                && !flags.intersects(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE)
+            {
+                indices.transfer_family = Some(i as u32);
+            }
+            if indices.compute_family.is_none()
+                && flags.contains(vk::QueueFlags::COMPUTE)
+                && !flags.contains(vk::QueueFlags::GRAPHICS)
+            {
+                indices.compute_family = Some(i as u32);
             }
         }
-    indices
+    Ok(indices)
 }
@@ -0,0 +1,409 @@
+use ash::version::InstanceV1_0;
+use ash::vk;
+use serde_json::{json, Map, Value};
+use std::ffi::CStr;
+
+use crate::cleanup::CleanupGuard;
+use crate::config::CoreConfig;
+use crate::debug::{DebugCallbackData, ValidationInfo};
+use crate::error::{RendererResult, VkResultExt};
+use crate::instance::{
+    available_instance_extensions, create_instance, format_version, negotiate_api_version,
+};
+use crate::physical_device::{device_name, rate_device};
+use crate::surface::{create_surface, SurfaceStuff};
+use crate::swapchain::query_swapchain_support;
+
+// Builds a JSON object with one entry per listed field, named like the Rust field.
+macro_rules! json_fields {
+    ($value:expr, [$($field:ident),* $(,)?]) => {{
+        let mut fields = Map::new();
+        $(fields.insert(stringify!($field).to_string(), json!($value.$field));)*
+        Value::Object(fields)
+    }};
+    ($value:expr, bool [$($field:ident),* $(,)?]) => {{
+        let mut fields = Map::new();
+        $(fields.insert(stringify!($field).to_string(), json!($value.$field == vk::TRUE));)*
+        Value::Object(fields)
+    }};
+    ($value:expr, flags [$($field:ident),* $(,)?]) => {{
+        let mut fields = Map::new();
+        $(fields.insert(stringify!($field).to_string(), json!(format!("{:?}", $value.$field)));)*
+        Value::Object(fields)
+    }};
+}
+
+// A vulkaninfo-style dump of everything the renderer looks at when picking a device, meant to
+// be attached to bug reports. Surface formats and present modes are only reported with a window.
+pub fn capability_report(
+    config: &CoreConfig,
+    window: Option<&winit::window::Window>,
+) -> RendererResult<Value> {
+    let entry = ash::Entry::new()?;
+    // the report should not be interleaved with validation output
+    let validation = ValidationInfo::default().with_enable(false);
+    let debug_callback_data = DebugCallbackData::new(&validation);
+    // tears the instance and surface down once the report is built, declared after the
+    // callback data so the instance is gone before it is freed
+    let mut guard = CleanupGuard::new();
+    let api_version = negotiate_api_version(&entry, config.app_info.max_api_version)?;
+    let instance = create_instance(
+        &entry,
+        window,
+        &config.app_info,
+        api_version,
+        &validation,
+        &debug_callback_data,
+    )?;
+    guard.push_with(&instance, |instance| unsafe { instance.destroy_instance(None) });
+    let surface = match window {
+        Some(window) => Some(create_surface(&entry, &instance, window)?),
+        None => None,
+    };
+    if let Some(surface) = &surface {
+        let surface_handle = surface.surface;
+        guard.push_with(&surface.surface_loader, move |surface_loader| unsafe {
+            surface_loader.destroy_surface(surface_handle, None)
+        });
+    }
+
+    let devices = physical_devices_report(&instance, surface.as_ref(), config)?;
+    Ok(json!({
+        "api_version": format_version(api_version),
+        "instance_extensions": available_instance_extensions(&entry, &validation)?,
+        "physical_devices": devices,
+    }))
+}
+
+fn physical_devices_report(
+    instance: &ash::Instance,
+    surface: Option<&SurfaceStuff>,
+    config: &CoreConfig,
+) -> RendererResult<Vec<Value>> {
+    let physical_devices = unsafe {
+        instance
+            .enumerate_physical_devices()
+            .context("Failed to enumerate physical devices.")?
+    };
+    let mut devices = vec![];
+    for (index, &physical_device) in physical_devices.iter().enumerate() {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let features = unsafe { instance.get_physical_device_features(physical_device) };
+        let memory_properties =
+            unsafe { instance.get_physical_device_memory_properties(physical_device) };
+        let extensions = unsafe {
+            instance
+                .enumerate_device_extension_properties(physical_device)
+                .context("Failed to enumerate device extensions")?
+        };
+        let mut device = json!({
+            "index": index,
+            "name": device_name(&properties),
+            "device_type": format!("{:?}", properties.device_type),
+            "api_version": format_version(properties.api_version),
+            "driver_version": properties.driver_version,
+            "vendor_id": properties.vendor_id,
+            "device_id": properties.device_id,
+            "pipeline_cache_uuid": properties
+                .pipeline_cache_uuid
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>(),
+            "score": rate_device(instance, physical_device, &config.device_selection),
+            "limits": limits_report(&properties.limits),
+            "features": features_report(&features),
+            "queue_families": queue_families_report(instance, physical_device, surface)?,
+            "memory_heaps": memory_properties.memory_heaps
+                [..memory_properties.memory_heap_count as usize]
+                .iter()
+                .map(|heap| json!({
+                    "size": heap.size,
+                    "flags": format!("{:?}", heap.flags),
+                }))
+                .collect::<Vec<_>>(),
+            "memory_types": memory_properties.memory_types
+                [..memory_properties.memory_type_count as usize]
+                .iter()
+                .map(|memory_type| json!({
+                    "heap_index": memory_type.heap_index,
+                    "property_flags": format!("{:?}", memory_type.property_flags),
+                }))
+                .collect::<Vec<_>>(),
+            "extensions": extensions
+                .iter()
+                .map(|extension| json!({
+                    "name": unsafe { CStr::from_ptr(extension.extension_name.as_ptr()) }
+                        .to_string_lossy(),
+                    "spec_version": extension.spec_version,
+                }))
+                .collect::<Vec<_>>(),
+        });
+        if let Some(surface) = surface {
+            device["surface"] = surface_report(physical_device, surface)?;
+        }
+        devices.push(device);
+    }
+    Ok(devices)
+}
+
+// Every VkPhysicalDeviceLimits field, the Bool32 and sample count ones in their own groups.
+fn limits_report(limits: &vk::PhysicalDeviceLimits) -> Value {
+    let mut report = json_fields!(limits, [
+        max_image_dimension1_d,
+        max_image_dimension2_d,
+        max_image_dimension3_d,
+        max_image_dimension_cube,
+        max_image_array_layers,
+        max_texel_buffer_elements,
+        max_uniform_buffer_range,
+        max_storage_buffer_range,
+        max_push_constants_size,
+        max_memory_allocation_count,
+        max_sampler_allocation_count,
+        buffer_image_granularity,
+        sparse_address_space_size,
+        max_bound_descriptor_sets,
+        max_per_stage_descriptor_samplers,
+        max_per_stage_descriptor_uniform_buffers,
+        max_per_stage_descriptor_storage_buffers,
+        max_per_stage_descriptor_sampled_images,
+        max_per_stage_descriptor_storage_images,
+        max_per_stage_descriptor_input_attachments,
+        max_per_stage_resources,
+        max_descriptor_set_samplers,
+        max_descriptor_set_uniform_buffers,
+        max_descriptor_set_uniform_buffers_dynamic,
+        max_descriptor_set_storage_buffers,
+        max_descriptor_set_storage_buffers_dynamic,
+        max_descriptor_set_sampled_images,
+        max_descriptor_set_storage_images,
+        max_descriptor_set_input_attachments,
+        max_vertex_input_attributes,
+        max_vertex_input_bindings,
+        max_vertex_input_attribute_offset,
+        max_vertex_input_binding_stride,
+        max_vertex_output_components,
+        max_tessellation_generation_level,
+        max_tessellation_patch_size,
+        max_tessellation_control_per_vertex_input_components,
+        max_tessellation_control_per_vertex_output_components,
+        max_tessellation_control_per_patch_output_components,
+        max_tessellation_control_total_output_components,
+        max_tessellation_evaluation_input_components,
+        max_tessellation_evaluation_output_components,
+        max_geometry_shader_invocations,
+        max_geometry_input_components,
+        max_geometry_output_components,
+        max_geometry_output_vertices,
+        max_geometry_total_output_components,
+        max_fragment_input_components,
+        max_fragment_output_attachments,
+        max_fragment_dual_src_attachments,
+        max_fragment_combined_output_resources,
+        max_compute_shared_memory_size,
+        max_compute_work_group_count,
+        max_compute_work_group_invocations,
+        max_compute_work_group_size,
+        sub_pixel_precision_bits,
+        sub_texel_precision_bits,
+        mipmap_precision_bits,
+        max_draw_indexed_index_value,
+        max_draw_indirect_count,
+        max_sampler_lod_bias,
+        max_sampler_anisotropy,
+        max_viewports,
+        max_viewport_dimensions,
+        viewport_bounds_range,
+        viewport_sub_pixel_bits,
+        min_memory_map_alignment,
+        min_texel_buffer_offset_alignment,
+        min_uniform_buffer_offset_alignment,
+        min_storage_buffer_offset_alignment,
+        min_texel_offset,
+        max_texel_offset,
+        min_texel_gather_offset,
+        max_texel_gather_offset,
+        min_interpolation_offset,
+        max_interpolation_offset,
+        sub_pixel_interpolation_offset_bits,
+        max_framebuffer_width,
+        max_framebuffer_height,
+        max_framebuffer_layers,
+        max_color_attachments,
+        max_sample_mask_words,
+        timestamp_period,
+        max_clip_distances,
+        max_cull_distances,
+        max_combined_clip_and_cull_distances,
+        discrete_queue_priorities,
+        point_size_range,
+        line_width_range,
+        point_size_granularity,
+        line_width_granularity,
+        optimal_buffer_copy_offset_alignment,
+        optimal_buffer_copy_row_pitch_alignment,
+        non_coherent_atom_size,
+    ]);
+    let bools = json_fields!(limits, bool [
+        timestamp_compute_and_graphics,
+        strict_lines,
+        standard_sample_locations,
+    ]);
+    let sample_counts = json_fields!(limits, flags [
+        framebuffer_color_sample_counts,
+        framebuffer_depth_sample_counts,
+        framebuffer_stencil_sample_counts,
+        framebuffer_no_attachments_sample_counts,
+        sampled_image_color_sample_counts,
+        sampled_image_integer_sample_counts,
+        sampled_image_depth_sample_counts,
+        sampled_image_stencil_sample_counts,
+        storage_image_sample_counts,
+    ]);
+    for fields in [bools, sample_counts].iter() {
+        if let (Value::Object(report), Value::Object(fields)) = (&mut report, fields) {
+            report.extend(fields.clone());
+        }
+    }
+    report
+}
+
+fn features_report(features: &vk::PhysicalDeviceFeatures) -> Value {
+    json_fields!(features, bool [
+        robust_buffer_access,
+        full_draw_index_uint32,
+        image_cube_array,
+        independent_blend,
+        geometry_shader,
+        tessellation_shader,
+        sample_rate_shading,
+        dual_src_blend,
+        logic_op,
+        multi_draw_indirect,
+        draw_indirect_first_instance,
+        depth_clamp,
+        depth_bias_clamp,
+        fill_mode_non_solid,
+        depth_bounds,
+        wide_lines,
+        large_points,
+        alpha_to_one,
+        multi_viewport,
+        sampler_anisotropy,
+        texture_compression_etc2,
+        texture_compression_astc_ldr,
+        texture_compression_bc,
+        occlusion_query_precise,
+        pipeline_statistics_query,
+        vertex_pipeline_stores_and_atomics,
+        fragment_stores_and_atomics,
+        shader_tessellation_and_geometry_point_size,
+        shader_image_gather_extended,
+        shader_storage_image_extended_formats,
+        shader_storage_image_multisample,
+        shader_storage_image_read_without_format,
+        shader_storage_image_write_without_format,
+        shader_uniform_buffer_array_dynamic_indexing,
+        shader_sampled_image_array_dynamic_indexing,
+        shader_storage_buffer_array_dynamic_indexing,
+        shader_storage_image_array_dynamic_indexing,
+        shader_clip_distance,
+        shader_cull_distance,
+        shader_float64,
+        shader_int64,
+        shader_int16,
+        shader_resource_residency,
+        shader_resource_min_lod,
+        sparse_binding,
+        sparse_residency_buffer,
+        sparse_residency_image2_d,
+        sparse_residency_image3_d,
+        sparse_residency2_samples,
+        sparse_residency4_samples,
+        sparse_residency8_samples,
+        sparse_residency16_samples,
+        sparse_residency_aliased,
+        variable_multisample_rate,
+        inherited_queries,
+    ])
+}
+
+fn queue_families_report(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    surface: Option<&SurfaceStuff>,
+) -> RendererResult<Vec<Value>> {
+    let queue_families =
+        unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+    let mut report = vec![];
+    for (index, queue_family) in queue_families.iter().enumerate() {
+        let granularity = queue_family.min_image_transfer_granularity;
+        let mut family = json!({
+            "index": index,
+            "queue_flags": format!("{:?}", queue_family.queue_flags),
+            "queue_count": queue_family.queue_count,
+            "timestamp_valid_bits": queue_family.timestamp_valid_bits,
+            "min_image_transfer_granularity":
+                [granularity.width, granularity.height, granularity.depth],
+        });
+        if let Some(surface) = surface {
+            family["present_support"] = json!(unsafe {
+                surface.surface_loader.get_physical_device_surface_support(
+                    physical_device,
+                    index as u32,
+                    surface.surface,
+                )
+            });
+        }
+        report.push(family);
+    }
+    Ok(report)
+}
+
+fn surface_report(
+    physical_device: vk::PhysicalDevice,
+    surface: &SurfaceStuff,
+) -> RendererResult<Value> {
+    let support = query_swapchain_support(physical_device, surface)?;
+    let capabilities = &support.capabilities;
+    Ok(json!({
+        "min_image_count": capabilities.min_image_count,
+        "max_image_count": capabilities.max_image_count,
+        "min_image_extent": [
+            capabilities.min_image_extent.width,
+            capabilities.min_image_extent.height,
+        ],
+        "max_image_extent": [
+            capabilities.max_image_extent.width,
+            capabilities.max_image_extent.height,
+        ],
+        "supported_usage_flags": format!("{:?}", capabilities.supported_usage_flags),
+        "formats": support
+            .formats
+            .iter()
+            .map(|format| json!({
+                "format": format!("{:?}", format.format),
+                "color_space": format!("{:?}", format.color_space),
+            }))
+            .collect::<Vec<_>>(),
+        "present_modes": support
+            .present_modes
+            .iter()
+            .map(|present_mode| format!("{:?}", present_mode))
+            .collect::<Vec<_>>(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_report_has_every_field() {
+        // VkPhysicalDeviceLimits has 106 members
+        let report = limits_report(&vk::PhysicalDeviceLimits::default());
+        assert_eq!(report.as_object().map(|fields| fields.len()), Some(106));
+        assert_eq!(report["strict_lines"], json!(false));
+        assert!(report["framebuffer_color_sample_counts"].is_string());
+    }
+}
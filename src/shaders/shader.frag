@@ -1,7 +1,10 @@
 #version 450
 
+layout(binding = 1) uniform sampler2D texSampler;
+
 layout (location =0) out vec4 outColor;
 layout(location = 0) in vec3 fragColor;
+layout(location = 1) in vec2 fragTexCoord;
 void main(){
-    outColor = vec4(fragColor, 1.0);
-}
\ No newline at end of file
+    outColor = vec4(fragColor, 1.0) * texture(texSampler, fragTexCoord);
+}
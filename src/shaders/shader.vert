@@ -1,17 +1,26 @@
 #version 450
 
-vec2 positions[3] = vec2[](
-    vec2(0.0,-0.5),
-    vec2(0.5,0.5),
-    vec2(-0.5,0.5)
-);
-vec3 colors[3] = vec3[](
-    vec3(1.0, 0.0, 0.0),
-    vec3(0.0, 1.0, 0.0),
-    vec3(0.0, 0.0, 1.0)
-);
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+} ubo;
+
+layout(push_constant) uniform PushConstants {
+    vec4 tint;
+    float time;
+    uint objectIndex;
+} push;
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inColor;
+layout(location = 2) in vec2 inTexCoord;
+layout(location = 3) in vec3 inNormal;
+
 layout(location = 0) out vec3 fragColor;
+layout(location = 1) out vec2 fragTexCoord;
 void main(){
-    gl_Position = vec4(positions[gl_VertexIndex],0.0,1.0);
-    fragColor = colors[gl_VertexIndex];
-}
\ No newline at end of file
+    gl_Position = ubo.proj * ubo.view * ubo.model * vec4(inPosition,1.0);
+    fragColor = inColor * push.tint.rgb;
+    fragTexCoord = inTexCoord;
+}
@@ -1,7 +1,9 @@
-use ash::version::{EntryV1_0,InstanceV1_0};
+use ash::version::EntryV1_0;
 use ash::vk;
-use winit;
-use ash::extensions::{ext, khr};
+use ash::extensions::khr;
+use std::ffi::CStr;
+
+use crate::error::{RendererError, RendererResult, VkResultExt};
 // pub struct Surface{
 //     surface:ash::vk::SurfaceKHR,
 //     surface_loader: ash::extensions::khr::Surface,
@@ -10,15 +12,54 @@ use ash::extensions::{ext, khr};
 pub struct SurfaceStuff {
     pub surface_loader: ash::extensions::khr::Surface,
     pub surface: vk::SurfaceKHR,
+}
+#[cfg(target_os = "windows")]
+pub fn required_extension_names(
+    _entry: &ash::Entry,
+    _window: &winit::window::Window,
+) -> RendererResult<Vec<*const i8>> {
+    Ok(vec![khr::Surface::name().as_ptr(), khr::Win32Surface::name().as_ptr()])
+}
+
+#[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+pub fn required_extension_names(
+    entry: &ash::Entry,
+    window: &winit::window::Window,
+) -> RendererResult<Vec<*const i8>> {
+    use winit::platform::unix::WindowExtUnix;
 
-    pub screen_width: u32,
-    pub screen_height: u32,
+    let platform_extension = if window.wayland_surface().is_some() {
+        khr::WaylandSurface::name()
+    } else {
+        x11_surface_extension(entry)?
+    };
+    Ok(vec![khr::Surface::name().as_ptr(), platform_extension.as_ptr()])
+}
+
+// winit hands out both an Xlib display and an XCB connection for X11 windows,
+// prefer Xlib and fall back to XCB when the loader does not expose it.
+#[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+fn x11_surface_extension(entry: &ash::Entry) -> RendererResult<&'static CStr> {
+    let available_extensions = entry
+        .enumerate_instance_extension_properties()
+        .context("Failed to enumerate Instance Extensions Properties!")?;
+    let is_xlib_available = available_extensions.iter().any(|extension| {
+        let extension_name = unsafe { CStr::from_ptr(extension.extension_name.as_ptr()) };
+        extension_name == khr::XlibSurface::name()
+    });
+    if is_xlib_available {
+        Ok(khr::XlibSurface::name())
+    } else {
+        Ok(khr::XcbSurface::name())
+    }
 }
-pub unsafe fn create_surface_a<E: EntryV1_0, I: InstanceV1_0>(
+
+#[cfg(target_os = "windows")]
+pub unsafe fn create_surface_a<E: EntryV1_0, I: ash::version::InstanceV1_0>(
     entry: &E,
     instance: &I,
     window: &winit::window::Window,
-) -> Result<vk::SurfaceKHR, vk::Result> {
+) -> RendererResult<vk::SurfaceKHR> {
     use std::os::raw::c_void;
     use std::ptr;
     use winapi::shared::windef::HWND;
@@ -35,25 +76,76 @@ pub unsafe fn create_surface_a<E: EntryV1_0, I: InstanceV1_0>(
         hwnd: hwnd as *const c_void,
     };
     let win32_surface_loader = khr::Win32Surface::new(entry, instance);
-    win32_surface_loader.create_win32_surface(&win32_create_info, None)
+    win32_surface_loader
+        .create_win32_surface(&win32_create_info, None)
+        .context("Failed to create surface.")
+}
+
+#[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+pub unsafe fn create_surface_a(
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+    window: &winit::window::Window,
+) -> RendererResult<vk::SurfaceKHR> {
+    use std::ptr;
+    use winit::platform::unix::WindowExtUnix;
+
+    if let (Some(display), Some(surface)) = (window.wayland_display(), window.wayland_surface()) {
+        let wayland_create_info = vk::WaylandSurfaceCreateInfoKHR {
+            s_type: vk::StructureType::WAYLAND_SURFACE_CREATE_INFO_KHR,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            display,
+            surface,
+        };
+        let wayland_surface_loader = khr::WaylandSurface::new(entry, instance);
+        return wayland_surface_loader
+            .create_wayland_surface(&wayland_create_info, None)
+            .context("Failed to create surface.");
+    }
+
+    let x11_window = window
+        .xlib_window()
+        .ok_or(RendererError::SurfaceLost)?;
+    if x11_surface_extension(entry)? == khr::XlibSurface::name() {
+        let x11_display = window.xlib_display().ok_or(RendererError::SurfaceLost)?;
+        let xlib_create_info = vk::XlibSurfaceCreateInfoKHR {
+            s_type: vk::StructureType::XLIB_SURFACE_CREATE_INFO_KHR,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            window: x11_window as vk::Window,
+            dpy: x11_display as *mut vk::Display,
+        };
+        let xlib_surface_loader = khr::XlibSurface::new(entry, instance);
+        xlib_surface_loader
+            .create_xlib_surface(&xlib_create_info, None)
+            .context("Failed to create surface.")
+    } else {
+        let xcb_connection = window.xcb_connection().ok_or(RendererError::SurfaceLost)?;
+        let xcb_create_info = vk::XcbSurfaceCreateInfoKHR {
+            s_type: vk::StructureType::XCB_SURFACE_CREATE_INFO_KHR,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            window: x11_window as vk::xcb_window_t,
+            connection: xcb_connection as *mut vk::xcb_connection_t,
+        };
+        let xcb_surface_loader = khr::XcbSurface::new(entry, instance);
+        xcb_surface_loader
+            .create_xcb_surface(&xcb_create_info, None)
+            .context("Failed to create surface.")
+    }
 }
 
 pub fn create_surface(
     entry: &ash::Entry,
     instance: &ash::Instance,
     window: &winit::window::Window,
-    screen_width: u32,
-    screen_height: u32,
-) -> SurfaceStuff {
-    let surface = unsafe {
-        create_surface_a(entry, instance, window).expect("Failed to create surface.")
-    };
+) -> RendererResult<SurfaceStuff> {
+    let surface = unsafe { create_surface_a(entry, instance, window)? };
     let surface_loader = ash::extensions::khr::Surface::new(entry, instance);
 
-    SurfaceStuff {
+    Ok(SurfaceStuff {
         surface_loader,
         surface,
-        screen_width,
-        screen_height,
-    }
+    })
 }
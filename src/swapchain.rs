@@ -1,12 +1,9 @@
-use crate::queue::find_queue_families;
-use crate::{physical_device, surface};
-use ash::extensions::khr;
-use ash::version::{DeviceV1_0, InstanceV1_0};
+use ash::version::DeviceV1_0;
 use ash::vk;
-use num::clamp;
 use std::ptr;
 use crate::queue::QueueFamilyIndices;
 use crate::surface::SurfaceStuff;
+use crate::error::{RendererResult, VkResultExt};
 pub struct SwapChainSupportDetails {
     pub capabilities: vk::SurfaceCapabilitiesKHR,
     pub formats: Vec<vk::SurfaceFormatKHR>,
@@ -171,7 +168,7 @@ pub struct SwapChainStuff {
 //         composite_alpha: vk::CompositeAlphaFlagsKHR::OPAQUE,
 //         present_mode: present_mode,
 //         clipped: vk::TRUE,
-//         old_swapchain: vk::SwapchainKHR::null(),
+//         old_swapchain,
 //         ..Default::default()
 //     };
 //     let swap_chain_loader = khr::Swapchain::new(instance,device);
@@ -198,26 +195,26 @@ pub struct SwapChainStuff {
 pub fn query_swapchain_support(
     physical_device: vk::PhysicalDevice,
     surface_stuff: &SurfaceStuff,
-) -> SwapChainSupportDetails {
+) -> RendererResult<SwapChainSupportDetails> {
     unsafe {
         let capabilities = surface_stuff
             .surface_loader
             .get_physical_device_surface_capabilities(physical_device, surface_stuff.surface)
-            .expect("Failed to query for surface capabilities.");
+            .context("Failed to query for surface capabilities.")?;
         let formats = surface_stuff
             .surface_loader
             .get_physical_device_surface_formats(physical_device, surface_stuff.surface)
-            .expect("Failed to query for surface formats.");
+            .context("Failed to query for surface formats.")?;
         let present_modes = surface_stuff
             .surface_loader
             .get_physical_device_surface_present_modes(physical_device, surface_stuff.surface)
-            .expect("Failed to query for surface present mode.");
+            .context("Failed to query for surface present mode.")?;
 
-        SwapChainSupportDetails {
+        Ok(SwapChainSupportDetails {
             capabilities,
             formats,
             present_modes,
-        }
+        })
     }
 }
 
@@ -228,8 +225,9 @@ pub fn create_swapchain(
     window: &winit::window::Window,
     surface_stuff: &SurfaceStuff,
     queue_family: &QueueFamilyIndices,
-) -> SwapChainStuff {
-    let swapchain_support = query_swapchain_support(physical_device, surface_stuff);
+    old_swapchain: vk::SwapchainKHR,
+) -> RendererResult<SwapChainStuff> {
+    let swapchain_support = query_swapchain_support(physical_device, surface_stuff)?;
 
     let surface_format = choose_swapchain_format(&swapchain_support.formats);
     let present_mode = choose_swapchain_present_mode(&swapchain_support.present_modes);
@@ -273,7 +271,7 @@ pub fn create_swapchain(
         composite_alpha: vk::CompositeAlphaFlagsKHR::OPAQUE,
         present_mode,
         clipped: vk::TRUE,
-        old_swapchain: vk::SwapchainKHR::null(),
+        old_swapchain,
         image_array_layers: 1,
     };
 
@@ -281,41 +279,41 @@ pub fn create_swapchain(
     let swapchain = unsafe {
         swapchain_loader
             .create_swapchain(&swapchain_create_info, None)
-            .expect("Failed to create Swapchain!")
+            .context("Failed to create Swapchain!")?
     };
 
     let swapchain_images = unsafe {
         swapchain_loader
             .get_swapchain_images(swapchain)
-            .expect("Failed to get Swapchain Images.")
+            .context("Failed to get Swapchain Images.")?
     };
 
-    SwapChainStuff {
+    Ok(SwapChainStuff {
         swapchain_loader,
         swapchain,
         swapchain_format: surface_format.format,
         swapchain_extent: extent,
         swapchain_images,
-    }
+    })
 }
 
 pub fn choose_swapchain_format(
-    available_formats: &Vec<vk::SurfaceFormatKHR>,
+    available_formats: &[vk::SurfaceFormatKHR],
 ) -> vk::SurfaceFormatKHR {
 
     for available_format in available_formats {
         if available_format.format == vk::Format::B8G8R8A8_SRGB
             && available_format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
         {
-            return available_format.clone();
+            return *available_format;
         }
     }
 
-    return available_formats.first().unwrap().clone();
+    *available_formats.first().unwrap()
 }
 
 pub fn choose_swapchain_present_mode(
-    available_present_modes: &Vec<vk::PresentModeKHR>,
+    available_present_modes: &[vk::PresentModeKHR],
 ) -> vk::PresentModeKHR {
     for &available_present_mode in available_present_modes.iter() {
         if available_present_mode == vk::PresentModeKHR::MAILBOX {
@@ -330,7 +328,7 @@ pub fn choose_swapchain_extent(
     capabilities: &vk::SurfaceCapabilitiesKHR,
     window: &winit::window::Window,
 ) -> vk::Extent2D {
-    if capabilities.current_extent.width != u32::max_value() {
+    if capabilities.current_extent.width != u32::MAX {
         capabilities.current_extent
     } else {
         use num::clamp;
@@ -344,12 +342,12 @@ pub fn choose_swapchain_extent(
 
         vk::Extent2D {
             width: clamp(
-                window_size.width as u32,
+                window_size.width,
                 capabilities.min_image_extent.width,
                 capabilities.max_image_extent.width,
             ),
             height: clamp(
-                window_size.height as u32,
+                window_size.height,
                 capabilities.min_image_extent.height,
                 capabilities.max_image_extent.height,
             ),
@@ -360,22 +358,21 @@ pub fn choose_swapchain_extent(
 pub fn create_image_views(
     device: &ash::Device,
     surface_format: vk::Format,
-    images: &Vec<vk::Image>,
-) -> Vec<vk::ImageView> {
-    let swapchain_imageviews: Vec<vk::ImageView> = images
-        .iter()
-        .map(|&image| {
-            create_image_view(
-                device,
-                image,
-                surface_format,
-                vk::ImageAspectFlags::COLOR,
-                1,
-            )
-        })
-        .collect();
-
-    swapchain_imageviews
+    images: &[vk::Image],
+) -> RendererResult<Vec<vk::ImageView>> {
+    let mut image_views = vec![];
+    for &image in images.iter() {
+        match create_image_view(device, image, surface_format, vk::ImageAspectFlags::COLOR, 1) {
+            Ok(image_view) => image_views.push(image_view),
+            Err(err) => {
+                for image_view in image_views {
+                    unsafe { device.destroy_image_view(image_view, None) };
+                }
+                return Err(err);
+            }
+        }
+    }
+    Ok(image_views)
 }
 
 pub fn create_image_view(
@@ -384,7 +381,7 @@ pub fn create_image_view(
     format: vk::Format,
     aspect_flags: vk::ImageAspectFlags,
     mip_levels: u32,
-) -> vk::ImageView {
+) -> RendererResult<vk::ImageView> {
     let imageview_create_info = vk::ImageViewCreateInfo {
         s_type: vk::StructureType::IMAGE_VIEW_CREATE_INFO,
         p_next: ptr::null(),
@@ -410,6 +407,6 @@ pub fn create_image_view(
     unsafe {
         device
             .create_image_view(&imageview_create_info, None)
-            .expect("Failed to create Image View!")
+            .context("Failed to create Image View!")
     }
 }
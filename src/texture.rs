@@ -0,0 +1,671 @@
+use ash::version::{DeviceV1_0, InstanceV1_0};
+use ash::vk;
+use std::path::Path;
+
+use crate::allocator::{Allocation, Allocator};
+use crate::buffer::create_staging_buffer;
+use crate::command::{begin_single_time_command, end_single_time_command};
+use crate::device_features::{DeviceFeature, EnabledDeviceFeatures};
+use crate::error::{RendererError, RendererResult, VkResultExt};
+use crate::swapchain::create_image_view;
+
+const TEXTURE_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
+// How textures are filtered and wrapped, set up like CoreConfig.
+#[derive(Debug, Clone, Copy)]
+pub struct SamplerConfig {
+    pub mag_filter: vk::Filter,
+    pub min_filter: vk::Filter,
+    pub mipmap_mode: vk::SamplerMipmapMode,
+    pub address_mode: vk::SamplerAddressMode,
+    // needs DeviceFeature::SamplerAnisotropy, clamped to maxSamplerAnisotropy
+    pub max_anisotropy: Option<f32>,
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        SamplerConfig {
+            mag_filter: vk::Filter::LINEAR,
+            min_filter: vk::Filter::LINEAR,
+            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+            address_mode: vk::SamplerAddressMode::REPEAT,
+            max_anisotropy: None,
+        }
+    }
+}
+
+impl SamplerConfig {
+    // Starts from the defaults and applies, when set:
+    //   VULKAN_SAMPLER_FILTER=nearest|linear
+    //   VULKAN_SAMPLER_ADDRESS_MODE=repeat|mirrored_repeat|clamp_to_edge|clamp_to_border
+    //   VULKAN_SAMPLER_ANISOTROPY=16, also needs VULKAN_DEVICE_FEATURES=samplerAnisotropy
+    pub fn from_env() -> Self {
+        let mut config = SamplerConfig::default();
+        if let Ok(value) = std::env::var("VULKAN_SAMPLER_FILTER") {
+            match parse_filter(&value) {
+                Some((filter, mipmap_mode)) => {
+                    config = config
+                        .with_filter(filter, filter)
+                        .with_mipmap_mode(mipmap_mode)
+                }
+                None => log::warn!("Ignoring invalid VULKAN_SAMPLER_FILTER value: {}", value),
+            }
+        }
+        if let Ok(value) = std::env::var("VULKAN_SAMPLER_ADDRESS_MODE") {
+            match parse_address_mode(&value) {
+                Some(address_mode) => config = config.with_address_mode(address_mode),
+                None => log::warn!("Ignoring invalid VULKAN_SAMPLER_ADDRESS_MODE value: {}", value),
+            }
+        }
+        if let Ok(value) = std::env::var("VULKAN_SAMPLER_ANISOTROPY") {
+            match value.trim().parse::<f32>() {
+                Ok(max_anisotropy) if max_anisotropy >= 1.0 => {
+                    config = config.with_anisotropy(Some(max_anisotropy))
+                }
+                _ => log::warn!("Ignoring invalid VULKAN_SAMPLER_ANISOTROPY value: {}", value),
+            }
+        }
+        config
+    }
+
+    pub fn with_filter(mut self, mag_filter: vk::Filter, min_filter: vk::Filter) -> Self {
+        self.mag_filter = mag_filter;
+        self.min_filter = min_filter;
+        self
+    }
+
+    pub fn with_mipmap_mode(mut self, mipmap_mode: vk::SamplerMipmapMode) -> Self {
+        self.mipmap_mode = mipmap_mode;
+        self
+    }
+
+    pub fn with_address_mode(mut self, address_mode: vk::SamplerAddressMode) -> Self {
+        self.address_mode = address_mode;
+        self
+    }
+
+    pub fn with_anisotropy(mut self, max_anisotropy: Option<f32>) -> Self {
+        self.max_anisotropy = max_anisotropy;
+        self
+    }
+}
+
+// the mipmap mode follows the filter, so "nearest" really is blocky
+fn parse_filter(value: &str) -> Option<(vk::Filter, vk::SamplerMipmapMode)> {
+    match value.trim().to_lowercase().as_str() {
+        "nearest" => Some((vk::Filter::NEAREST, vk::SamplerMipmapMode::NEAREST)),
+        "linear" => Some((vk::Filter::LINEAR, vk::SamplerMipmapMode::LINEAR)),
+        _ => None,
+    }
+}
+
+fn parse_address_mode(value: &str) -> Option<vk::SamplerAddressMode> {
+    match value.trim().to_lowercase().replace('-', "_").as_str() {
+        "repeat" => Some(vk::SamplerAddressMode::REPEAT),
+        "mirrored_repeat" => Some(vk::SamplerAddressMode::MIRRORED_REPEAT),
+        "clamp_to_edge" => Some(vk::SamplerAddressMode::CLAMP_TO_EDGE),
+        "clamp_to_border" => Some(vk::SamplerAddressMode::CLAMP_TO_BORDER),
+        _ => None,
+    }
+}
+
+// Sampled through the Context's sampler, so a texture is just the image and its view.
+pub struct TextureStuff {
+    pub image: vk::Image,
+    pub allocation: Allocation,
+    pub image_view: vk::ImageView,
+}
+
+// Loads a PNG or JPEG (anything the image crate decodes) into a sampled texture.
+pub fn load_texture(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    device: &ash::Device,
+    allocator: &mut Allocator,
+    command_pool: vk::CommandPool,
+    submit_queue: vk::Queue,
+    path: &Path,
+) -> RendererResult<TextureStuff> {
+    let image = image::open(path)
+        .map_err(|source| RendererError::ImageLoad {
+            path: path.display().to_string(),
+            source,
+        })?
+        .to_rgba();
+    create_texture(
+        instance,
+        physical_device,
+        device,
+        allocator,
+        command_pool,
+        submit_queue,
+        &image,
+    )
+}
+
+// A 1x1 opaque white texture, sampling it leaves the vertex colors untouched.
+pub fn create_white_texture(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    device: &ash::Device,
+    allocator: &mut Allocator,
+    command_pool: vk::CommandPool,
+    submit_queue: vk::Queue,
+) -> RendererResult<TextureStuff> {
+    let image = image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 255, 255, 255]));
+    create_texture(
+        instance,
+        physical_device,
+        device,
+        allocator,
+        command_pool,
+        submit_queue,
+        &image,
+    )
+}
+
+// the full chain down to 1x1
+fn mip_levels_for(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+// Blitting between mip levels needs BLIT_SRC/BLIT_DST, and LINEAR filtering on top of that.
+fn is_linear_blit_supported(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    format: vk::Format,
+) -> bool {
+    let format_properties =
+        unsafe { instance.get_physical_device_format_properties(physical_device, format) };
+    format_properties.optimal_tiling_features.contains(
+        vk::FormatFeatureFlags::BLIT_SRC
+            | vk::FormatFeatureFlags::BLIT_DST
+            | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+    )
+}
+
+// Every level resized from the base image on the CPU, packed one after another.
+// Returns the data and the (offset, extent) of each level inside it.
+fn generate_mip_chain_on_cpu(
+    image: &image::RgbaImage,
+    mip_levels: u32,
+) -> (Vec<u8>, Vec<(vk::DeviceSize, vk::Extent2D)>) {
+    let (width, height) = image.dimensions();
+    let mut data = image.to_vec();
+    let mut levels = vec![(0, vk::Extent2D { width, height })];
+    for level in 1..mip_levels {
+        let level_width = (width >> level).max(1);
+        let level_height = (height >> level).max(1);
+        let resized = image::imageops::resize(
+            image,
+            level_width,
+            level_height,
+            image::FilterType::Triangle,
+        );
+        levels.push((
+            data.len() as vk::DeviceSize,
+            vk::Extent2D {
+                width: level_width,
+                height: level_height,
+            },
+        ));
+        data.extend_from_slice(&resized.into_raw());
+    }
+    (data, levels)
+}
+
+// Uploads the RGBA8 pixels of `image` through a staging buffer. The mip chain is
+// blitted on the GPU when the format allows it, otherwise resized on the CPU.
+pub fn create_texture(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    device: &ash::Device,
+    allocator: &mut Allocator,
+    command_pool: vk::CommandPool,
+    submit_queue: vk::Queue,
+    image: &image::RgbaImage,
+) -> RendererResult<TextureStuff> {
+    let (width, height) = image.dimensions();
+    let extent = vk::Extent2D { width, height };
+    let mip_levels = mip_levels_for(width, height);
+    let is_blit_supported = is_linear_blit_supported(instance, physical_device, TEXTURE_FORMAT);
+    let cpu_mip_chain = if is_blit_supported || mip_levels == 1 {
+        None
+    } else {
+        log::info!(
+            "{:?} does not support linear blits, generating mipmaps on the CPU",
+            TEXTURE_FORMAT
+        );
+        Some(generate_mip_chain_on_cpu(image, mip_levels))
+    };
+    let (staging_data, levels) = match &cpu_mip_chain {
+        Some((data, levels)) => (&data[..], levels.clone()),
+        None => (&**image, vec![(0, extent)]),
+    };
+    let (staging_buffer, staging_allocation) = create_staging_buffer(allocator, staging_data)?;
+
+    let image_create_info = vk::ImageCreateInfo {
+        image_type: vk::ImageType::TYPE_2D,
+        format: TEXTURE_FORMAT,
+        extent: vk::Extent3D {
+            width,
+            height,
+            depth: 1,
+        },
+        mip_levels,
+        array_layers: 1,
+        samples: vk::SampleCountFlags::TYPE_1,
+        tiling: vk::ImageTiling::OPTIMAL,
+        // TRANSFER_SRC because each mip level is blitted from the one above it
+        usage: vk::ImageUsageFlags::TRANSFER_SRC
+            | vk::ImageUsageFlags::TRANSFER_DST
+            | vk::ImageUsageFlags::SAMPLED,
+        sharing_mode: vk::SharingMode::EXCLUSIVE,
+        initial_layout: vk::ImageLayout::UNDEFINED,
+        ..Default::default()
+    };
+    let (image, allocation) =
+        match allocator.create_image(&image_create_info, vk::MemoryPropertyFlags::DEVICE_LOCAL) {
+            Ok(image) => image,
+            Err(err) => {
+                allocator.destroy_buffer(staging_buffer, &staging_allocation);
+                return Err(err);
+            }
+        };
+
+    let result = upload_texture(
+        device,
+        command_pool,
+        submit_queue,
+        staging_buffer,
+        image,
+        &levels,
+        mip_levels,
+    );
+    allocator.destroy_buffer(staging_buffer, &staging_allocation);
+    if let Err(err) = result {
+        allocator.destroy_image(image, &allocation);
+        return Err(err);
+    }
+
+    let image_view = match create_image_view(
+        device,
+        image,
+        TEXTURE_FORMAT,
+        vk::ImageAspectFlags::COLOR,
+        mip_levels,
+    ) {
+        Ok(image_view) => image_view,
+        Err(err) => {
+            allocator.destroy_image(image, &allocation);
+            return Err(err);
+        }
+    };
+
+    Ok(TextureStuff {
+        image,
+        allocation,
+        image_view,
+    })
+}
+
+fn upload_texture(
+    device: &ash::Device,
+    command_pool: vk::CommandPool,
+    submit_queue: vk::Queue,
+    staging_buffer: vk::Buffer,
+    image: vk::Image,
+    levels: &[(vk::DeviceSize, vk::Extent2D)],
+    mip_levels: u32,
+) -> RendererResult<()> {
+    let command_buffer = begin_single_time_command(device, command_pool)?;
+    transition_image_layout(
+        device,
+        command_buffer,
+        image,
+        vk::ImageLayout::UNDEFINED,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        mip_levels,
+    );
+    for (mip_level, &(buffer_offset, extent)) in levels.iter().enumerate() {
+        copy_buffer_to_image(
+            device,
+            command_buffer,
+            staging_buffer,
+            buffer_offset,
+            image,
+            mip_level as u32,
+            extent,
+        );
+    }
+    // only level 0 was uploaded, the rest is blitted from it
+    if (levels.len() as u32) < mip_levels {
+        generate_mipmaps(device, command_buffer, image, levels[0].1, mip_levels);
+    } else {
+        transition_image_layout(
+            device,
+            command_buffer,
+            image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            mip_levels,
+        );
+    }
+    end_single_time_command(device, submit_queue, command_pool, command_buffer)
+}
+
+// Records a barrier for the two transitions an upload needs, on every mip level.
+fn transition_image_layout(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    image: vk::Image,
+    old_layout: vk::ImageLayout,
+    new_layout: vk::ImageLayout,
+    mip_levels: u32,
+) {
+    let (src_access_mask, dst_access_mask, src_stage, dst_stage) = match (old_layout, new_layout) {
+        (vk::ImageLayout::UNDEFINED, vk::ImageLayout::TRANSFER_DST_OPTIMAL) => (
+            vk::AccessFlags::empty(),
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            vk::PipelineStageFlags::TRANSFER,
+        ),
+        (vk::ImageLayout::TRANSFER_DST_OPTIMAL, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL) => (
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::AccessFlags::SHADER_READ,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+        ),
+        _ => panic!(
+            "Unsupported layout transition from {:?} to {:?}",
+            old_layout, new_layout
+        ),
+    };
+    let image_barriers = [vk::ImageMemoryBarrier {
+        src_access_mask,
+        dst_access_mask,
+        old_layout,
+        new_layout,
+        src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        image,
+        subresource_range: vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: mip_levels,
+            base_array_layer: 0,
+            layer_count: 1,
+        },
+        ..Default::default()
+    }];
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            src_stage,
+            dst_stage,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &image_barriers,
+        );
+    }
+}
+
+// Blits each level from the previous one. Expects every level in TRANSFER_DST_OPTIMAL
+// and leaves them all in SHADER_READ_ONLY_OPTIMAL.
+fn generate_mipmaps(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    image: vk::Image,
+    extent: vk::Extent2D,
+    mip_levels: u32,
+) {
+    let mut image_barrier = vk::ImageMemoryBarrier {
+        src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        image,
+        subresource_range: vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        },
+        ..Default::default()
+    };
+    let mut mip_width = extent.width as i32;
+    let mut mip_height = extent.height as i32;
+
+    for i in 1..mip_levels {
+        let next_width = (mip_width / 2).max(1);
+        let next_height = (mip_height / 2).max(1);
+
+        image_barrier.subresource_range.base_mip_level = i - 1;
+        image_barrier.old_layout = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
+        image_barrier.new_layout = vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
+        image_barrier.src_access_mask = vk::AccessFlags::TRANSFER_WRITE;
+        image_barrier.dst_access_mask = vk::AccessFlags::TRANSFER_READ;
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[image_barrier],
+            );
+        }
+
+        let blits = [vk::ImageBlit {
+            src_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: i - 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            src_offsets: [
+                vk::Offset3D { x: 0, y: 0, z: 0 },
+                vk::Offset3D {
+                    x: mip_width,
+                    y: mip_height,
+                    z: 1,
+                },
+            ],
+            dst_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: i,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            dst_offsets: [
+                vk::Offset3D { x: 0, y: 0, z: 0 },
+                vk::Offset3D {
+                    x: next_width,
+                    y: next_height,
+                    z: 1,
+                },
+            ],
+        }];
+        unsafe {
+            device.cmd_blit_image(
+                command_buffer,
+                image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &blits,
+                vk::Filter::LINEAR,
+            );
+        }
+
+        // level i - 1 is final now
+        image_barrier.old_layout = vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
+        image_barrier.new_layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+        image_barrier.src_access_mask = vk::AccessFlags::TRANSFER_READ;
+        image_barrier.dst_access_mask = vk::AccessFlags::SHADER_READ;
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[image_barrier],
+            );
+        }
+
+        mip_width = next_width;
+        mip_height = next_height;
+    }
+
+    // the last level is only ever blitted to
+    image_barrier.subresource_range.base_mip_level = mip_levels - 1;
+    image_barrier.old_layout = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
+    image_barrier.new_layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+    image_barrier.src_access_mask = vk::AccessFlags::TRANSFER_WRITE;
+    image_barrier.dst_access_mask = vk::AccessFlags::SHADER_READ;
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[image_barrier],
+        );
+    }
+}
+
+// copies one mip level, starting at `buffer_offset` in the staging buffer
+fn copy_buffer_to_image(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    buffer: vk::Buffer,
+    buffer_offset: vk::DeviceSize,
+    image: vk::Image,
+    mip_level: u32,
+    extent: vk::Extent2D,
+) {
+    let buffer_image_regions = [vk::BufferImageCopy {
+        buffer_offset,
+        buffer_row_length: 0,
+        buffer_image_height: 0,
+        image_subresource: vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            mip_level,
+            base_array_layer: 0,
+            layer_count: 1,
+        },
+        image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+        image_extent: vk::Extent3D {
+            width: extent.width,
+            height: extent.height,
+            depth: 1,
+        },
+    }];
+    unsafe {
+        device.cmd_copy_buffer_to_image(
+            command_buffer,
+            buffer,
+            image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &buffer_image_regions,
+        );
+    }
+}
+
+// One sampler serves every texture, max_lod is left open so it covers any mip chain.
+pub fn create_sampler(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    device: &ash::Device,
+    sampler_config: &SamplerConfig,
+    enabled_features: &EnabledDeviceFeatures,
+) -> RendererResult<vk::Sampler> {
+    let is_anisotropy_enabled =
+        enabled_features.is_feature_enabled(DeviceFeature::SamplerAnisotropy);
+    let max_anisotropy = match sampler_config.max_anisotropy {
+        Some(max_anisotropy) if is_anisotropy_enabled => {
+            let limits = unsafe { instance.get_physical_device_properties(physical_device) }.limits;
+            Some(max_anisotropy.min(limits.max_sampler_anisotropy))
+        }
+        Some(_) => {
+            log::warn!("Anisotropic filtering requested, but samplerAnisotropy is not enabled");
+            None
+        }
+        None => None,
+    };
+    let sampler_create_info = vk::SamplerCreateInfo {
+        mag_filter: sampler_config.mag_filter,
+        min_filter: sampler_config.min_filter,
+        mipmap_mode: sampler_config.mipmap_mode,
+        address_mode_u: sampler_config.address_mode,
+        address_mode_v: sampler_config.address_mode,
+        address_mode_w: sampler_config.address_mode,
+        mip_lod_bias: 0.0,
+        anisotropy_enable: max_anisotropy.is_some() as vk::Bool32,
+        max_anisotropy: max_anisotropy.unwrap_or(1.0),
+        compare_enable: vk::FALSE,
+        compare_op: vk::CompareOp::ALWAYS,
+        min_lod: 0.0,
+        max_lod: vk::LOD_CLAMP_NONE,
+        border_color: vk::BorderColor::INT_OPAQUE_BLACK,
+        unnormalized_coordinates: vk::FALSE,
+        ..Default::default()
+    };
+    unsafe {
+        device
+            .create_sampler(&sampler_create_info, None)
+            .context("Failed to create Sampler!")
+    }
+}
+
+pub fn destroy_texture(device: &ash::Device, allocator: &mut Allocator, texture: &TextureStuff) {
+    unsafe { device.destroy_image_view(texture.image_view, None) };
+    allocator.destroy_image(texture.image, &texture.allocation);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mip_levels_for_extent() {
+        assert_eq!(mip_levels_for(1, 1), 1);
+        assert_eq!(mip_levels_for(2, 2), 2);
+        assert_eq!(mip_levels_for(1024, 1024), 11);
+        // the larger side decides, non-powers of two round down
+        assert_eq!(mip_levels_for(1024, 768), 11);
+        assert_eq!(mip_levels_for(300, 5), 9);
+        assert_eq!(mip_levels_for(1, 513), 10);
+    }
+
+    #[test]
+    fn cpu_mip_chain_offsets() {
+        let image = image::RgbaImage::from_pixel(4, 3, image::Rgba([255; 4]));
+        let (data, levels) = generate_mip_chain_on_cpu(&image, mip_levels_for(4, 3));
+        let extents: Vec<_> = levels
+            .iter()
+            .map(|(offset, extent)| (*offset, extent.width, extent.height))
+            .collect();
+        assert_eq!(extents, vec![(0, 4, 3), (48, 2, 1), (56, 1, 1)]);
+        assert_eq!(data.len(), 60);
+    }
+
+    #[test]
+    fn parse_sampler_options() {
+        assert_eq!(
+            parse_filter(" Nearest "),
+            Some((vk::Filter::NEAREST, vk::SamplerMipmapMode::NEAREST))
+        );
+        assert_eq!(parse_filter("cubic"), None);
+        assert_eq!(
+            parse_address_mode("clamp-to-edge"),
+            Some(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        );
+        assert_eq!(parse_address_mode("wrap"), None);
+    }
+}
@@ -0,0 +1,143 @@
+use ash::version::DeviceV1_0;
+use ash::vk;
+use cgmath::{Deg, Matrix4, Point3, Vector3};
+
+use crate::allocator::{Allocation, Allocator};
+use crate::descriptor::{create_descriptor_pool, create_descriptor_sets};
+use crate::error::RendererResult;
+
+// cgmath follows OpenGL: Y up in clip space and depth in [-1, 1]. Vulkan wants Y down and [0, 1].
+#[rustfmt::skip]
+pub const OPENGL_TO_VULKAN_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, -1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+// matches the std140 block in shader.vert
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct UniformBufferObject {
+    pub model: Matrix4<f32>,
+    pub view: Matrix4<f32>,
+    pub proj: Matrix4<f32>,
+}
+
+impl UniformBufferObject {
+    pub fn new(model: Matrix4<f32>, view: Matrix4<f32>, extent: vk::Extent2D) -> Self {
+        let aspect = extent.width as f32 / extent.height.max(1) as f32;
+        UniformBufferObject {
+            model,
+            view,
+            proj: OPENGL_TO_VULKAN_MATRIX * cgmath::perspective(Deg(45.0), aspect, 0.1, 10.0),
+        }
+    }
+}
+
+// looks at the origin from +Z, Y up
+pub fn default_view() -> Matrix4<f32> {
+    Matrix4::look_at(
+        Point3::new(0.0, 0.0, 2.0),
+        Point3::new(0.0, 0.0, 0.0),
+        Vector3::new(0.0, 1.0, 0.0),
+    )
+}
+
+// One host visible buffer per swapchain image, so updating the one for the image being
+// recorded never races a frame still in flight.
+pub fn create_uniform_buffers(
+    allocator: &mut Allocator,
+    count: usize,
+) -> RendererResult<Vec<(vk::Buffer, Allocation)>> {
+    let buffer_size = std::mem::size_of::<UniformBufferObject>() as vk::DeviceSize;
+    let mut uniform_buffers = vec![];
+    for _ in 0..count {
+        match allocator.create_buffer(
+            buffer_size,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        ) {
+            Ok(uniform_buffer) => uniform_buffers.push(uniform_buffer),
+            Err(err) => {
+                for (buffer, allocation) in uniform_buffers {
+                    allocator.destroy_buffer(buffer, &allocation);
+                }
+                return Err(err);
+            }
+        }
+    }
+    Ok(uniform_buffers)
+}
+
+pub fn update_uniform_buffer(allocation: &Allocation, ubo: &UniformBufferObject) {
+    if let Some(data_ptr) = allocation.mapped_ptr() {
+        unsafe {
+            (data_ptr as *mut UniformBufferObject).copy_from_nonoverlapping(ubo, 1);
+        }
+    }
+}
+
+// The uniform buffers of every image and the descriptor sets pointing at them, in the
+// same order. Core keeps them across a swapchain rebuild unless the image count changes.
+pub struct UniformStuff {
+    pub uniform_buffers: Vec<(vk::Buffer, Allocation)>,
+    pub descriptor_pool: vk::DescriptorPool,
+    pub descriptor_sets: Vec<vk::DescriptorSet>,
+}
+
+pub fn create_uniform_stuff(
+    device: &ash::Device,
+    allocator: &mut Allocator,
+    descriptor_set_layout: &vk::DescriptorSetLayout,
+    count: usize,
+    texture_info: &vk::DescriptorImageInfo,
+) -> RendererResult<UniformStuff> {
+    let mut uniform_stuff = UniformStuff {
+        uniform_buffers: create_uniform_buffers(allocator, count)?,
+        descriptor_pool: vk::DescriptorPool::null(),
+        descriptor_sets: vec![],
+    };
+    let buffers: Vec<vk::Buffer> = uniform_stuff
+        .uniform_buffers
+        .iter()
+        .map(|(buffer, _)| *buffer)
+        .collect();
+    let result = create_descriptor_pool(device, count).and_then(|descriptor_pool| {
+        uniform_stuff.descriptor_pool = descriptor_pool;
+        create_descriptor_sets(
+            device,
+            &descriptor_pool,
+            descriptor_set_layout,
+            &buffers,
+            texture_info,
+        )
+    });
+    match result {
+        Ok(descriptor_sets) => {
+            uniform_stuff.descriptor_sets = descriptor_sets;
+            Ok(uniform_stuff)
+        }
+        Err(err) => {
+            destroy_uniform_stuff(device, allocator, &mut uniform_stuff);
+            Err(err)
+        }
+    }
+}
+
+// Leaves `uniform_stuff` empty, so calling it again is a no-op.
+pub fn destroy_uniform_stuff(
+    device: &ash::Device,
+    allocator: &mut Allocator,
+    uniform_stuff: &mut UniformStuff,
+) {
+    unsafe {
+        // frees the descriptor sets along with it
+        device.destroy_descriptor_pool(uniform_stuff.descriptor_pool, None);
+    }
+    uniform_stuff.descriptor_pool = vk::DescriptorPool::null();
+    uniform_stuff.descriptor_sets.clear();
+    for (buffer, allocation) in uniform_stuff.uniform_buffers.drain(..) {
+        allocator.destroy_buffer(buffer, &allocation);
+    }
+}
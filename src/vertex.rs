@@ -0,0 +1,76 @@
+use ash::vk;
+use memoffset::offset_of;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Vertex {
+    pub pos: [f32; 3],
+    pub color: [f32; 3],
+    pub tex_coord: [f32; 2],
+    pub normal: [f32; 3],
+}
+
+impl Vertex {
+    pub fn get_binding_description() -> [vk::VertexInputBindingDescription; 1] {
+        [vk::VertexInputBindingDescription {
+            binding: 0,
+            stride: std::mem::size_of::<Vertex>() as u32,
+            input_rate: vk::VertexInputRate::VERTEX,
+        }]
+    }
+
+    // locations match the inputs of shader.vert
+    pub fn get_attribute_descriptions() -> [vk::VertexInputAttributeDescription; 4] {
+        [
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 0,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: offset_of!(Vertex, pos) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 1,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: offset_of!(Vertex, color) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 2,
+                format: vk::Format::R32G32_SFLOAT,
+                offset: offset_of!(Vertex, tex_coord) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 3,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: offset_of!(Vertex, normal) as u32,
+            },
+        ]
+    }
+}
+
+// the triangle that used to be hardcoded in shader.vert, in Y up world space facing +Z
+pub const VERTICES_DATA: [Vertex; 3] = [
+    Vertex {
+        pos: [0.0, 0.5, 0.0],
+        color: [1.0, 0.0, 0.0],
+        tex_coord: [0.5, 0.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        pos: [0.5, -0.5, 0.0],
+        color: [0.0, 1.0, 0.0],
+        tex_coord: [1.0, 1.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        pos: [-0.5, -0.5, 0.0],
+        color: [0.0, 0.0, 1.0],
+        tex_coord: [0.0, 1.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+];
+
+// counter-clockwise, like OBJ files
+pub const INDICES_DATA: [u32; 3] = [0, 2, 1];